@@ -18,7 +18,8 @@ for t in ${canisters[@]}; do
 
     mkdir -p wasm
     cp -r target/wasm32-unknown-unknown/release/$t.wasm wasm/$t.wasm
-    gzip -c wasm/$t.wasm > wasm/$t.wasm.gz
+    # -n leaves out the name and timestamp, so the same wasm always results in the same hash
+    gzip -n -c wasm/$t.wasm > wasm/$t.wasm.gz
 
 done
 
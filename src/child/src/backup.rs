@@ -5,7 +5,11 @@ use ic_scalable_canister::{ic_scalable_misc, store::Data};
 use ic_stable_structures::StableBTreeMap;
 use shared::member_model::Member;
 
-use crate::store::{ENTRIES, ENTRIES_MEMORY_ID, MEMORY_MANAGER, STABLE_DATA};
+use crate::{
+    certification::Certification,
+    snapshot::SnapshotCache,
+    store::{ENTRIES, ENTRIES_MEMORY_ID, MEMORY_MANAGER, STABLE_DATA},
+};
 
 //
 #[update(guard = "is_owner")]
@@ -40,6 +44,9 @@ pub fn restore_data() {
             e.borrow_mut().insert(entry.0.to_string(), entry.1.clone());
         });
     });
+
+    Certification::rebuild();
+    SnapshotCache::clear();
 }
 
 // #[update(guard = "is_owner")]
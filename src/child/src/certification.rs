@@ -0,0 +1,210 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::Bound,
+    time::Duration,
+};
+
+use candid::Principal;
+use ic_cdk::api::{data_certificate, set_certified_data};
+use ic_cdk_timers::set_timer;
+use ic_certified_map::{labeled, labeled_hash, AsHashTree, Hash, RbTree};
+use serde::Serialize;
+use shared::{
+    certification::{
+        count_hash, count_key, member_key, roles_hash, CertifiedResponse, MEMBERSHIP_LABEL,
+    },
+    member_model::Member,
+};
+
+use crate::store::ENTRIES;
+
+// The amount of member entries that are added to the certified tree per timer run of a rebuild
+static REBUILD_BATCH_SIZE: usize = 1_000;
+
+#[derive(Clone, Debug, PartialEq)]
+enum RebuildState {
+    Done,
+    // The key of the last member entry that is added to the certified tree, `None` when the rebuild did not start yet
+    InProgress(Option<String>),
+}
+
+thread_local! {
+    // Certified tree with the roles per group member and the member count per group
+    // not stored because it is rebuilt from the member entries on post_upgrade and restore
+    static TREE: RefCell<RbTree<Vec<u8>, Hash>> = const { RefCell::new(RbTree::new()) };
+
+    // The groups that are certified per member principal, used to remove stale keys when a member changes
+    static MEMBER_GROUPS: RefCell<HashMap<Principal, Vec<Principal>>> = RefCell::new(HashMap::new());
+
+    // The certified member count per group
+    static GROUP_COUNTS: RefCell<HashMap<Principal, u64>> = RefCell::new(HashMap::new());
+
+    // Progress of the rebuild of the certified tree
+    static REBUILD: RefCell<RebuildState> = const { RefCell::new(RebuildState::Done) };
+}
+
+// Keeps the certified data of this canister in sync with the member entries
+// the certified tree is labeled `membership` and contains;
+// - `member/{group_identifier}/{principal}` with the hash of the roles of the member within the group
+// - `count/{group_identifier}` with the hash of the amount of members of the group (absent when 0)
+pub struct Certification;
+
+impl Certification {
+    // Method to update the certified keys of a member, used after every write of a member entry
+    // during a rebuild the certified data is set once the rebuild is done
+    pub fn certify_member(member: &Member) {
+        Self::update_member(member);
+        if !Self::is_rebuilding() {
+            Self::set_certified_data();
+        }
+    }
+
+    // Method to rebuild the certified tree from all stored member entries
+    // the entries are added in batches of `REBUILD_BATCH_SIZE` by a timer, so the instruction limit is not hit on large canisters
+    pub fn rebuild() {
+        TREE.with(|t| *t.borrow_mut() = RbTree::new());
+        MEMBER_GROUPS.with(|m| m.borrow_mut().clear());
+        GROUP_COUNTS.with(|c| c.borrow_mut().clear());
+
+        // A running rebuild continues from the start with its already scheduled timer
+        let schedule = !Self::is_rebuilding();
+        REBUILD.with(|r| *r.borrow_mut() = RebuildState::InProgress(None));
+        if schedule {
+            set_timer(Duration::ZERO, Self::rebuild_batch);
+        }
+    }
+
+    fn rebuild_batch() {
+        let last_key = match REBUILD.with(|r| r.borrow().clone()) {
+            RebuildState::Done => return,
+            RebuildState::InProgress(_last_key) => _last_key,
+        };
+
+        let start = match last_key {
+            Some(_key) => Bound::Excluded(_key),
+            None => Bound::Unbounded,
+        };
+
+        let batch: Vec<(String, Member)> = ENTRIES.with(|entries| {
+            entries
+                .borrow()
+                .range((start, Bound::Unbounded))
+                .take(REBUILD_BATCH_SIZE)
+                .collect()
+        });
+
+        for (_, member) in &batch {
+            Self::update_member(member);
+        }
+
+        match batch.len() < REBUILD_BATCH_SIZE {
+            true => {
+                REBUILD.with(|r| *r.borrow_mut() = RebuildState::Done);
+                Self::set_certified_data();
+            }
+            false => {
+                let last_key = batch.last().map(|(key, _)| key.clone());
+                REBUILD.with(|r| *r.borrow_mut() = RebuildState::InProgress(last_key));
+                set_timer(Duration::ZERO, Self::rebuild_batch);
+            }
+        }
+    }
+
+    fn is_rebuilding() -> bool {
+        REBUILD.with(|r| *r.borrow() != RebuildState::Done)
+    }
+
+    // Method to wrap data in a certified response with the witness for the given key
+    // the certificate is only available in query calls, so it is empty when called as update
+    // the certificate is also empty while the certified tree is rebuilt, the witness would not match the certified data
+    pub fn response<T>(data: T, key: &[u8]) -> CertifiedResponse<T> {
+        let witness = TREE.with(|t| {
+            let tree = t.borrow();
+            let witness = labeled(MEMBERSHIP_LABEL, tree.witness(key));
+
+            let mut serializer = serde_cbor::Serializer::new(vec![]);
+            let _ = serializer.self_describe();
+            witness
+                .serialize(&mut serializer)
+                .map(|_| serializer.into_inner())
+                .unwrap_or_default()
+        });
+
+        let certificate = match Self::is_rebuilding() {
+            true => vec![],
+            false => data_certificate().unwrap_or_default(),
+        };
+
+        CertifiedResponse {
+            data,
+            certificate,
+            witness,
+        }
+    }
+
+    fn update_member(member: &Member) {
+        let previous_groups = MEMBER_GROUPS
+            .with(|m| m.borrow_mut().remove(&member.principal))
+            .unwrap_or_default();
+        let current_groups: Vec<Principal> = member.joined.keys().cloned().collect();
+
+        TREE.with(|t| {
+            let mut tree = t.borrow_mut();
+
+            for group_identifier in &previous_groups {
+                tree.delete(&member_key(group_identifier, &member.principal));
+            }
+
+            for (group_identifier, join) in &member.joined {
+                tree.insert(
+                    member_key(group_identifier, &member.principal),
+                    roles_hash(&join.roles),
+                );
+            }
+        });
+
+        let changed_groups: HashSet<Principal> = previous_groups
+            .iter()
+            .filter(|group_identifier| !current_groups.contains(group_identifier))
+            .chain(
+                current_groups
+                    .iter()
+                    .filter(|group_identifier| !previous_groups.contains(group_identifier)),
+            )
+            .cloned()
+            .collect();
+
+        for group_identifier in changed_groups {
+            let count = GROUP_COUNTS.with(|c| {
+                let mut counts = c.borrow_mut();
+                let count = counts.entry(group_identifier).or_insert(0);
+                match current_groups.contains(&group_identifier) {
+                    true => *count += 1,
+                    false => *count = count.saturating_sub(1),
+                }
+                *count
+            });
+
+            TREE.with(|t| {
+                let mut tree = t.borrow_mut();
+                match count {
+                    0 => {
+                        tree.delete(&count_key(&group_identifier));
+                        GROUP_COUNTS.with(|c| c.borrow_mut().remove(&group_identifier));
+                    }
+                    _ => tree.insert(count_key(&group_identifier), count_hash(count)),
+                }
+            });
+        }
+
+        if !current_groups.is_empty() {
+            MEMBER_GROUPS.with(|m| m.borrow_mut().insert(member.principal, current_groups));
+        }
+    }
+
+    fn set_certified_data() {
+        let root_hash = TREE.with(|t| t.borrow().root_hash());
+        set_certified_data(&labeled_hash(MEMBERSHIP_LABEL, &root_hash));
+    }
+}
@@ -8,24 +8,31 @@ use ic_cdk::{
             provisional::CanisterIdRecord,
         },
     },
-    caller, id, init, query, update,
+    caller, id, init, post_upgrade, pre_upgrade, query, update,
 };
 
 use ic_scalable_canister::ic_scalable_misc::{
     enums::api_error_type::ApiError,
-    models::http_models::{HttpRequest, HttpResponse},
+    helpers::metrics_helper::PathEntry,
+    models::http_models::{HeaderField, HttpRequest, HttpResponse},
 };
 #[allow(unused_imports)]
 use ic_scalable_canister::{ic_methods, store::Data};
 
-use crate::store::{ENTRIES, STABLE_DATA};
+use crate::{
+    certification::Certification,
+    http_api::HttpApi,
+    metrics::Metrics,
+    rate_limit::RateLimiter,
+    store::{Store, ENTRIES, STABLE_DATA},
+};
 
 // This call get triggered when a new canister is spun up
 // the data is passed along to the new canister as a byte array
 #[update(guard = "auth")]
 async fn add_entry_by_parent(entry: Vec<u8>) -> Result<(), ApiError> {
     STABLE_DATA.with(|data| {
-        ENTRIES.with(|entries| Data::add_entry_by_parent(data, entries, caller(), entry, None))
+        ENTRIES.with(|entries| Store::add_entry_by_parent(data, entries, caller(), entry))
     })
 }
 
@@ -36,12 +43,34 @@ fn accept_cycles() -> u64 {
 }
 
 // HTTP request handler, canister metrics are added to the response by default
-// can be extended by adding `Vec<PathEntry>` as a third parameter
+// the domain metrics (joins, leaves, gated join failures, etc.) are served on `/member_metrics`
+// the `/groups` and `/members` paths are served by the read-only JSON API
 #[query]
 fn http_request(req: HttpRequest) -> HttpResponse {
+    if let Some(_response) = HttpApi::handle(&req) {
+        return _response;
+    }
+
+    let path_entries = vec![PathEntry {
+        match_path: vec!["member_metrics".to_string()],
+        response: HttpResponse {
+            status_code: 200,
+            headers: vec![HeaderField(
+                "content-type".to_string(),
+                "text/plain".to_string(),
+            )],
+            body: Metrics::render().as_bytes().to_vec(),
+        },
+    }];
+
     STABLE_DATA.with(|data| {
         ENTRIES.with(|entries| {
-            Data::http_request_with_metrics(data, entries.borrow().len() as usize, req, vec![])
+            Data::http_request_with_metrics(
+                data,
+                entries.borrow().len() as usize,
+                req,
+                path_entries,
+            )
         })
     })
 }
@@ -50,7 +79,12 @@ fn http_request(req: HttpRequest) -> HttpResponse {
 #[query(name = "__get_candid_interface_tmp_hack")]
 pub fn __export_did_tmp_() -> String {
     use candid::export_service;
+    use shared::capacity_model::GroupCapacity;
+    use shared::certification::CertifiedResponse;
+    use shared::chunk_model::{ChunkError, ChunkRequest, DataChunk};
+    use shared::invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest};
     use shared::member_model::*;
+    use std::collections::HashMap;
 
     use ic_canister_backup::models::*;
     use ic_cdk::api::management_canister::http_request::HttpResponse;
@@ -68,7 +102,26 @@ pub fn __export_did_tmp_() -> String {
 pub fn init(parent: Principal, name: String, identifier: usize) {
     STABLE_DATA.with(|data| {
         ic_methods::init(data, parent, name, identifier);
-    })
+    });
+
+    Store::schedule_reinstatements();
+}
+
+// Triggered before upgrading the canister, the rate limit buckets are kept in heap memory so a snapshot is stored
+#[pre_upgrade]
+pub fn pre_upgrade() {
+    RateLimiter::save_snapshot();
+}
+
+// Triggered after upgrading the canister, the data is kept in stable structures
+// the certified membership data is not stored, so it is rebuilt from the member entries by a timer
+// the reinstatement timers of muted and suspended members are scheduled again
+// the rate limit buckets are restored from the snapshot that is stored on pre_upgrade
+#[post_upgrade]
+pub fn post_upgrade() {
+    RateLimiter::restore_snapshot();
+    Certification::rebuild();
+    Store::schedule_reinstatements();
 }
 
 // Method used to save the candid interface to a file
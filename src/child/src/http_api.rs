@@ -0,0 +1,173 @@
+use candid::Principal;
+use ic_scalable_canister::ic_scalable_misc::models::http_models::{
+    HeaderField, HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+use shared::member_model::{InviteMemberResponse, JoinedMemberResponse};
+
+use crate::store::Store;
+
+static DEFAULT_LIMIT: usize = 20;
+static MAX_LIMIT: usize = 100;
+
+#[derive(Serialize)]
+struct JsonPage<T: Serialize> {
+    data: Vec<T>,
+    page: usize,
+    limit: usize,
+    total: usize,
+    number_of_pages: usize,
+}
+
+#[derive(Serialize)]
+struct JsonCount {
+    group_identifier: Principal,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct JsonMember {
+    principal: Principal,
+    joined: Vec<JoinedMemberResponse>,
+    invites: Vec<InviteMemberResponse>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    error: String,
+}
+
+// Read-only JSON API for off-chain indexers and analytics, routed from the `http_request` query
+// - GET /groups/{id}/members?page=1&limit=20
+// - GET /groups/{id}/invites/count
+// - GET /members/{principal}
+pub struct HttpApi;
+
+impl HttpApi {
+    // Method to handle a request of the JSON API, returns `None` when the path is not part of the API
+    pub fn handle(req: &HttpRequest) -> Option<HttpResponse> {
+        let (path, query) = match req.url.split_once('?') {
+            Some((_path, _query)) => (_path, _query),
+            None => (req.url.as_str(), ""),
+        };
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match segments.first() {
+            Some(&"groups") | Some(&"members") => {}
+            _ => return None,
+        }
+
+        if req.method.to_uppercase() != "GET" {
+            return Some(Self::error(405, "Only GET requests are supported"));
+        }
+
+        let response = match segments.as_slice() {
+            ["groups", group_identifier, "members"] => Self::parse_principal(group_identifier)
+                .and_then(|_group_identifier| {
+                    Self::parse_paging(query).map(|(page, limit)| {
+                        Self::paged(Store::get_group_members(_group_identifier), page, limit)
+                    })
+                }),
+            ["groups", group_identifier, "invites", "count"] => {
+                Self::parse_principal(group_identifier).map(|_group_identifier| {
+                    let count = Store::get_group_invites_count(vec![_group_identifier])
+                        .first()
+                        .map(|(_, count)| *count)
+                        .unwrap_or(0);
+                    Self::json(
+                        200,
+                        &JsonCount {
+                            group_identifier: _group_identifier,
+                            count,
+                        },
+                    )
+                })
+            }
+            ["members", principal] => Self::parse_principal(principal).map(|_principal| {
+                match Store::get_member_groups(_principal) {
+                    None => Self::error(404, "Member not found"),
+                    Some((joined, invites)) => Self::json(
+                        200,
+                        &JsonMember {
+                            principal: _principal,
+                            joined,
+                            invites,
+                        },
+                    ),
+                }
+            }),
+            _ => Err(Self::error(404, "Not found")),
+        };
+
+        Some(response.unwrap_or_else(|err| err))
+    }
+
+    fn parse_principal(value: &str) -> Result<Principal, HttpResponse> {
+        Principal::from_text(value)
+            .map_err(|_| Self::error(400, &format!("Invalid principal: {}", value)))
+    }
+
+    // Method to parse the `page` (starting at 1) and `limit` query parameters
+    fn parse_paging(query: &str) -> Result<(usize, usize), HttpResponse> {
+        let mut page = 1;
+        let mut limit = DEFAULT_LIMIT;
+
+        for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            let parsed = value.parse::<usize>();
+            match (key, parsed) {
+                ("page", Ok(_page)) if _page > 0 => page = _page,
+                ("limit", Ok(_limit)) if _limit > 0 && _limit <= MAX_LIMIT => limit = _limit,
+                ("page", _) => return Err(Self::error(400, "The page should be 1 or higher")),
+                ("limit", _) => {
+                    return Err(Self::error(
+                        400,
+                        &format!("The limit should be between 1 and {}", MAX_LIMIT),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok((page, limit))
+    }
+
+    fn paged<T: Serialize>(data: Vec<T>, page: usize, limit: usize) -> HttpResponse {
+        let total = data.len();
+        let data: Vec<T> = data
+            .into_iter()
+            .skip((page - 1) * limit)
+            .take(limit)
+            .collect();
+
+        Self::json(
+            200,
+            &JsonPage {
+                data,
+                page,
+                limit,
+                total,
+                number_of_pages: total.div_ceil(limit),
+            },
+        )
+    }
+
+    fn error(status_code: u16, message: &str) -> HttpResponse {
+        Self::json(
+            status_code,
+            &JsonError {
+                error: message.to_string(),
+            },
+        )
+    }
+
+    fn json<T: Serialize>(status_code: u16, body: &T) -> HttpResponse {
+        HttpResponse {
+            status_code,
+            headers: vec![HeaderField(
+                "content-type".to_string(),
+                "application/json".to_string(),
+            )],
+            body: serde_json::to_vec(body).unwrap_or_default(),
+        }
+    }
+}
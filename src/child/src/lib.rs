@@ -1,7 +1,12 @@
 pub static IDENTIFIER_KIND: &str = "mbr";
 
 pub mod backup;
+pub mod certification;
 pub mod default;
+pub mod http_api;
 pub mod methods;
+pub mod metrics;
+pub mod rate_limit;
+pub mod snapshot;
 pub mod stable_backup;
 pub mod store;
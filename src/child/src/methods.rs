@@ -1,30 +1,64 @@
+use std::collections::HashMap;
+
 use candid::Principal;
 use ic_cdk::{caller, query, update};
 use ic_scalable_canister::ic_scalable_misc::enums::api_error_type::ApiError;
 
-use shared::member_model::{InviteMemberResponse, JoinedMemberResponse, Member};
+use shared::{
+    capacity_model::GroupCapacity,
+    certification::{count_key, member_key, CertifiedResponse},
+    chunk_model::{ChunkError, ChunkRequest, DataChunk},
+    invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest},
+    member_model::{
+        ChildConfig, InviteMemberResponse, JoinAnswer, JoinedMemberResponse, Member,
+        MemberEntryResponse, MemberSearchResult, MemberStats, MemberStatus,
+    },
+};
 
 use crate::store::STABLE_DATA;
 
-use super::store::Store;
+use super::{
+    certification::Certification, metrics::Metrics, rate_limit::RateLimiter, store::Store,
+};
 
 // This method is used to join an existing group
 // The method is async because checks if the group exists and optionally creates a new canister
+// When this canister is at capacity the member is stored on a new sibling canister, which is returned in the response
 #[update(guard = "auth")]
 async fn join_group(
     group_identifier: Principal,
     account_identifier: Option<String>,
-) -> Result<(Principal, Member), ApiError> {
-    Store::join_group(caller(), group_identifier, account_identifier).await
+    answers: Option<Vec<JoinAnswer>>,
+) -> Result<MemberEntryResponse, ApiError> {
+    RateLimiter::check(caller(), Some(group_identifier), "join_group")?;
+    let result = Store::join_group(caller(), group_identifier, account_identifier, answers).await;
+    match &result {
+        // Joining a private group results in an invite request
+        Ok(_response) => match _response.member.joined.contains_key(&group_identifier) {
+            true => Metrics::increment("joins", &[]),
+            false => Metrics::increment("invites", &[("type", "user_request")]),
+        },
+        Err(err) => Metrics::increment_error(
+            "gated_join_failures",
+            err,
+            &["NOT_OWNING_NFT", "NOT_OWNING_NEURON"],
+        ),
+    }
+    Metrics::observe_instructions("join_group");
+    result
 }
 
-// This method is used to create an empty member when a profile is created (inter-canister call)
+// This method is used to create an empty member when a profile is created (inter-canister call from the profile canister)
 #[update(guard = "auth")]
 async fn create_empty_member(
     caller: Principal,
     profile_identifier: Principal,
-) -> Result<Principal, ApiError> {
-    Store::create_empty_member(caller, profile_identifier)
+) -> Result<MemberEntryResponse, ApiError> {
+    // Only the profile canister of the profile can create the member, so the `caller` argument can not be chosen freely
+    Store::check_profile_canister(ic_cdk::caller(), profile_identifier)?;
+    // Both the calling canister and the user the member is created for are limited
+    RateLimiter::check_principals(&[ic_cdk::caller(), caller], None, "create_empty_member")?;
+    Store::create_empty_member(caller, profile_identifier).await
 }
 
 // This method is used to invite a user to a group
@@ -33,10 +67,16 @@ async fn invite_to_group(
     member_principal: Principal,
     group_identifier: Principal,
 ) -> Result<(Principal, Member), ApiError> {
-    match Store::can_write_invite(caller(), group_identifier).await {
+    RateLimiter::check(caller(), Some(group_identifier), "invite_to_group")?;
+    let result = match Store::can_write_invite(caller(), group_identifier).await {
         Ok(_caller) => Store::invite_to_group(group_identifier, member_principal),
         Err(err) => Err(err),
+    };
+    if result.is_ok() {
+        Metrics::increment("invites", &[("type", "owner_request")]);
     }
+    Metrics::observe_instructions("invite_to_group");
+    result
 }
 
 // This method is used to accept an invite to a group as a admin
@@ -45,10 +85,15 @@ async fn accept_user_request_group_invite(
     member_principal: Principal,
     group_identifier: Principal,
 ) -> Result<(Principal, Member), ApiError> {
-    match Store::can_write_invite(caller(), group_identifier).await {
-        Ok(_) => Store::accept_user_request_group_invite(member_principal, group_identifier),
+    let result = match Store::can_write_invite(caller(), group_identifier).await {
+        Ok(_) => Store::accept_user_request_group_invite(member_principal, group_identifier).await,
         Err(err) => Err(err),
+    };
+    if result.is_ok() {
+        Metrics::increment("joins", &[]);
     }
+    Metrics::observe_instructions("accept_user_request_group_invite");
+    result
 }
 
 // This method is used to accept an invite to a group as a user
@@ -56,7 +101,12 @@ async fn accept_user_request_group_invite(
 async fn accept_owner_request_group_invite(
     group_identifier: Principal,
 ) -> Result<(Principal, Member), ApiError> {
-    Store::accept_owner_request_group_invite(caller(), group_identifier)
+    let result = Store::accept_owner_request_group_invite(caller(), group_identifier).await;
+    if result.is_ok() {
+        Metrics::increment("joins", &[]);
+    }
+    Metrics::observe_instructions("accept_owner_request_group_invite");
+    result
 }
 
 // This method is used a to add an owner to the member entry when a group is created (inter-canister call)
@@ -64,8 +114,13 @@ async fn accept_owner_request_group_invite(
 async fn add_owner(
     owner_principal: Principal,
     group_identifier: Principal,
-) -> Result<Principal, ApiError> {
-    Store::add_owner(owner_principal, group_identifier).await
+) -> Result<MemberEntryResponse, ApiError> {
+    let result = Store::add_owner(owner_principal, group_identifier).await;
+    if result.is_ok() {
+        Metrics::increment("joins", &[]);
+    }
+    Metrics::observe_instructions("add_owner");
+    result
 }
 
 // Method to assign a role to a specific group member
@@ -107,6 +162,144 @@ async fn set_roles(
     }
 }
 
+// Method to mute, suspend or reinstate a specific group member, requires the moderation permission
+// muted and suspended members are reinstated automatically when the status expires
+#[update(guard = "auth")]
+async fn set_member_status(
+    principal: Principal,
+    group_identifier: Principal,
+    status: MemberStatus,
+) -> Result<JoinedMemberResponse, ApiError> {
+    match Store::can_moderate_member(caller(), group_identifier).await {
+        Ok(_) => Store::set_member_status(principal, group_identifier, status),
+        Err(err) => Err(err),
+    }
+}
+
+// Method to update the per-group profile fields of the caller, a field with an empty value is removed
+#[update(guard = "auth")]
+async fn update_my_group_profile(
+    group_identifier: Principal,
+    fields: HashMap<String, String>,
+) -> Result<JoinedMemberResponse, ApiError> {
+    Store::update_my_group_profile(caller(), group_identifier, fields).await
+}
+
+// Method to update the per-group profile fields of a specific group member, including the admin-only fields
+#[update(guard = "auth")]
+async fn update_member_group_profile(
+    principal: Principal,
+    group_identifier: Principal,
+    fields: HashMap<String, String>,
+) -> Result<JoinedMemberResponse, ApiError> {
+    match Store::can_edit_member(caller(), group_identifier).await {
+        Ok(_caller) => {
+            Store::update_member_group_profile(principal, group_identifier, fields).await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Method to create an invite code for a group, the code can be redeemed on any child canister
+#[update(guard = "auth")]
+async fn create_invite_code(request: InviteCodeRequest) -> Result<InviteCode, ApiError> {
+    match Store::can_write_invite(caller(), request.group_identifier).await {
+        Ok(_caller) => Store::create_invite_code(_caller, request).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to get the invite codes of a group, including the revoked codes, newest first
+#[update(guard = "auth")]
+async fn get_invite_codes(group_identifier: Principal) -> Result<Vec<InviteCode>, ApiError> {
+    match Store::can_read_invite(caller(), group_identifier).await {
+        Ok(_) => Store::get_invite_codes(group_identifier).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to get the redemptions of an invite code of a group, newest first
+#[update(guard = "auth")]
+async fn get_invite_code_redemptions(
+    group_identifier: Principal,
+    code: String,
+) -> Result<Vec<InviteCodeRedemption>, ApiError> {
+    match Store::can_read_invite(caller(), group_identifier).await {
+        Ok(_) => Store::get_invite_code_redemptions(group_identifier, code).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to revoke an invite code of a group, the code can not be redeemed anymore
+#[update(guard = "auth")]
+async fn revoke_invite_code(
+    group_identifier: Principal,
+    code: String,
+) -> Result<InviteCode, ApiError> {
+    match Store::can_delete_invite(caller(), group_identifier).await {
+        Ok(_) => Store::revoke_invite_code(group_identifier, code).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to join a group with an invite code
+// When this canister is at capacity the member is stored on a new sibling canister, which is returned in the response
+#[update(guard = "auth")]
+async fn redeem_invite_code(code: String) -> Result<MemberEntryResponse, ApiError> {
+    let result = Store::redeem_invite_code(caller(), code).await;
+    if result.is_ok() {
+        Metrics::increment("joins", &[]);
+        Metrics::increment("invite_code_redemptions", &[]);
+    }
+    Metrics::observe_instructions("redeem_invite_code");
+    result
+}
+
+// Method to set or remove the capacity of a group, past the capacity new joins are added to a waitlist
+#[update(guard = "auth")]
+async fn set_group_capacity(
+    group_identifier: Principal,
+    capacity: Option<u64>,
+) -> Result<Option<GroupCapacity>, ApiError> {
+    match Store::can_edit_member(caller(), group_identifier).await {
+        Ok(_) => Store::set_group_capacity(group_identifier, capacity).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to get the capacity, taken seats and waitlist of a group
+#[update(guard = "auth")]
+async fn get_group_capacity(
+    group_identifier: Principal,
+) -> Result<Option<GroupCapacity>, ApiError> {
+    match Store::can_read_member(caller(), group_identifier).await {
+        Ok(_) => Store::get_group_capacity(group_identifier).await,
+        Err(err) => Err(err),
+    }
+}
+
+// Method to leave the waitlist of a group
+#[update(guard = "auth")]
+async fn leave_waitlist(group_identifier: Principal) -> Result<(), ApiError> {
+    Store::leave_waitlist(caller(), group_identifier).await
+}
+
+// Method to add a member that is promoted from the waitlist of a group (inter-canister call)
+#[update(guard = "is_parent")]
+async fn add_waitlisted_member(
+    group_identifier: Principal,
+    principal: Principal,
+    role: String,
+) -> Result<MemberEntryResponse, ApiError> {
+    let result = Store::add_waitlisted_member(group_identifier, principal, role).await;
+    if result.is_ok() {
+        Metrics::increment("joins", &[]);
+        Metrics::increment("waitlist_promotions", &[]);
+    }
+    Metrics::observe_instructions("add_waitlisted_member");
+    result
+}
+
 // Method to fetch a specific group member by user principal
 #[query]
 fn get_group_member(
@@ -140,6 +333,28 @@ fn get_group_members(group_identifier: Principal) -> Result<Vec<JoinedMemberResp
     Ok(Store::get_group_members(group_identifier))
 }
 
+// Method to search the members of a group by principal, profile identifier or display name, ranked by match quality
+#[query]
+fn search_group_members(
+    group_identifier: Principal,
+    query: String,
+    limit: usize,
+) -> Vec<MemberSearchResult> {
+    Store::search_group_members(group_identifier, query, limit)
+}
+
+// Method used by the profile canister of a member to push the display name for the member search (inter-canister call)
+#[update(guard = "auth")]
+fn set_member_display_name(principal: Principal, display_name: String) -> Result<(), ApiError> {
+    Store::set_member_display_name(caller(), principal, display_name)
+}
+
+// Method used by the parent canister to resolve the member entries of user principals across child canisters
+#[query(guard = "is_parent")]
+fn get_members_by_principals(principals: Vec<Principal>) -> Vec<(Principal, Member)> {
+    Store::get_members_by_principals(principals)
+}
+
 // Method to get the caller member entry
 #[query]
 fn get_self() -> Result<(Principal, Member), ApiError> {
@@ -155,16 +370,63 @@ fn get_member_roles(
     Store::get_member_roles(member_identifier, group_identifier)
 }
 
+// Certified variant of `get_group_member`, the response is `None` when the principal is not a member of the group
+// the witness proves the roles (or the absence) under `member/{group_identifier}/{principal}`
+#[query]
+fn get_group_member_certified(
+    principal: Principal,
+    group_identifier: Principal,
+) -> CertifiedResponse<Option<JoinedMemberResponse>> {
+    let member = Store::get_group_member_by_user_principal(principal, group_identifier).ok();
+    Certification::response(member, &member_key(&group_identifier, &principal))
+}
+
+// Certified variant of `get_member_roles`
+// the witness proves the roles (or the absence when there are no roles) under `member/{group_identifier}/{principal}`
+#[query]
+fn get_member_roles_certified(
+    member_identifier: Principal,
+    group_identifier: Principal,
+) -> Result<CertifiedResponse<(Principal, Vec<String>)>, String> {
+    Store::get_member_roles(member_identifier, group_identifier).map(|(_principal, _roles)| {
+        Certification::response(
+            (_principal, _roles),
+            &member_key(&group_identifier, &_principal),
+        )
+    })
+}
+
+// Certified member count of a group, the witness proves the count (or the absence when 0) under `count/{group_identifier}`
+#[query]
+fn get_group_members_count_certified(group_identifier: Principal) -> CertifiedResponse<u64> {
+    let count = Store::get_group_members_count(vec![group_identifier])
+        .first()
+        .map(|(_, _count)| *_count as u64)
+        .unwrap_or(0);
+    Certification::response(count, &count_key(&group_identifier))
+}
+
 // Method to let the caller leave a group
 #[update(guard = "auth")]
 fn leave_group(group_identifier: Principal) -> Result<(), ApiError> {
-    Store::leave_group(caller(), group_identifier)
+    let result = Store::leave_group(caller(), group_identifier);
+    if result.is_ok() {
+        Metrics::increment("leaves", &[]);
+    }
+    Metrics::observe_instructions("leave_group");
+    result
 }
 
 // Method to remove an outstanding invite for a group as a user
 #[update(guard = "auth")]
 fn remove_invite(group_identifier: Principal) -> Result<(), ApiError> {
-    Store::remove_invite(caller(), group_identifier)
+    RateLimiter::check(caller(), Some(group_identifier), "remove_invite")?;
+    let result = Store::remove_invite(caller(), group_identifier);
+    if result.is_ok() {
+        Metrics::increment("removals", &[("type", "invite")]);
+    }
+    Metrics::observe_instructions("remove_invite");
+    result
 }
 
 // Method to remove a member from a group
@@ -173,10 +435,15 @@ async fn remove_member_from_group(
     principal: Principal,
     group_identifier: Principal,
 ) -> Result<(), ApiError> {
-    match Store::can_delete_member(caller(), group_identifier).await {
+    let result = match Store::can_delete_member(caller(), group_identifier).await {
         Ok(_caller) => Store::remove_join_from_member(_caller, principal, group_identifier),
         Err(err) => Err(err),
+    };
+    if result.is_ok() {
+        Metrics::increment("removals", &[("type", "member")]);
     }
+    Metrics::observe_instructions("remove_member_from_group");
+    result
 }
 
 // Method to remove an outstanding invite for a group as a admin
@@ -185,10 +452,15 @@ async fn remove_member_invite_from_group(
     principal: Principal,
     group_identifier: Principal,
 ) -> Result<(), ApiError> {
-    match Store::can_delete_invite(caller(), group_identifier).await {
+    let result = match Store::can_delete_invite(caller(), group_identifier).await {
         Ok(_caller) => Store::remove_invite_from_member(principal, group_identifier),
         Err(err) => Err(err),
+    };
+    if result.is_ok() {
+        Metrics::increment("removals", &[("type", "invite")]);
     }
+    Metrics::observe_instructions("remove_member_invite_from_group");
+    result
 }
 
 // Method to get all group invites
@@ -196,13 +468,16 @@ async fn remove_member_invite_from_group(
 async fn get_group_invites(
     group_identifier: Principal,
 ) -> Result<Vec<InviteMemberResponse>, ApiError> {
-    match Store::can_read_invite(caller(), group_identifier).await {
+    let result = match Store::can_read_invite(caller(), group_identifier).await {
         Ok(_caller) => Ok(Store::get_group_invites(group_identifier)),
         Err(err) => Err(err),
-    }
+    };
+    Metrics::observe_instructions("get_group_invites");
+    result
 }
 
-// COMPOSITE_QUERY PREPARATION
+// COMPOSITE_QUERY PREPARATION (legacy, replaced by `get_join_data_chunk` and `get_invite_data_chunk`)
+// kept for parent canisters that are not upgraded to the versioned chunk protocol yet
 // This methods is used by the parent canister to get members the (this) child canister
 // Data serialized and send as byte array chunks ` (bytes, (start_chunk, end_chunk)) `
 // The parent canister can then deserialize the data and pass it to the frontend
@@ -216,10 +491,14 @@ fn get_chunked_join_data(
         return (vec![], (0, 0));
     }
 
+    // The chunk size is capped by the config pushed by the parent canister
+    let max_bytes_per_chunk =
+        max_bytes_per_chunk.min(Store::get_config().max_bytes_per_chunk as usize);
     Store::get_chunked_join_data(&group_identifier, chunk, max_bytes_per_chunk)
 }
 
-// COMPOSITE_QUERY PREPARATION
+// COMPOSITE_QUERY PREPARATION (legacy, replaced by `get_join_data_chunk` and `get_invite_data_chunk`)
+// kept for parent canisters that are not upgraded to the versioned chunk protocol yet
 // This methods is used by the parent canister to get members the (this) child canister
 // Data serialized and send as byte array chunks ` (bytes, (start_chunk, end_chunk)) `
 // The parent canister can then deserialize the data and pass it to the frontend
@@ -233,9 +512,64 @@ fn get_chunked_invite_data(
         return (vec![], (0, 0));
     }
 
+    // The chunk size is capped by the config pushed by the parent canister
+    let max_bytes_per_chunk =
+        max_bytes_per_chunk.min(Store::get_config().max_bytes_per_chunk as usize);
     Store::get_chunked_invite_data(&group_identifier, chunk, max_bytes_per_chunk)
 }
 
+// COMPOSITE_QUERY PREPARATION
+// Versioned chunk protocol used by the parent canister to get the members of a group from this child canister
+// every chunk carries the snapshot id, total byte length, chunk count and a checksum so the parent can detect changed data
+#[query]
+fn get_join_data_chunk(request: ChunkRequest) -> Result<DataChunk, ChunkError> {
+    if caller() != STABLE_DATA.with(|data| data.borrow().get().parent) {
+        return Err(ChunkError::Unauthorized);
+    }
+    Store::get_join_data_chunk(request)
+}
+
+// COMPOSITE_QUERY PREPARATION
+// Versioned chunk protocol used by the parent canister to get the invites of a group from this child canister
+#[query]
+fn get_invite_data_chunk(request: ChunkRequest) -> Result<DataChunk, ChunkError> {
+    if caller() != STABLE_DATA.with(|data| data.borrow().get().parent) {
+        return Err(ChunkError::Unauthorized);
+    }
+    Store::get_invite_data_chunk(request)
+}
+
+// Method used by the parent canister to push the runtime configuration (inter-canister call)
+#[update(guard = "is_parent")]
+fn set_config(config: ChildConfig) -> Result<(), ApiError> {
+    Store::set_config(config)
+}
+
+// Method to get the runtime configuration that is pushed by the parent canister
+#[query]
+fn get_config() -> ChildConfig {
+    Store::get_config()
+}
+
+// Method used by the parent canister to gather the fleet metrics
+#[query]
+fn get_member_stats() -> MemberStats {
+    Store::get_member_stats()
+}
+
+// Method used by the parent canister to check if this canister is healthy after an upgrade
+#[query]
+fn self_test() -> Result<(), String> {
+    Store::self_test()
+}
+
+pub fn is_parent() -> Result<(), String> {
+    match caller() == STABLE_DATA.with(|data| data.borrow().get().parent) {
+        true => Ok(()),
+        false => Err("Unauthorized".to_string()),
+    }
+}
+
 pub fn auth() -> Result<(), String> {
     match caller() == Principal::anonymous() {
         true => Err("Unauthorized".to_string()),
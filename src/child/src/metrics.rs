@@ -0,0 +1,232 @@
+use std::{borrow::Cow, cell::RefCell, collections::BTreeSet};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_cdk::api::{performance_counter, time};
+use ic_scalable_canister::ic_scalable_misc::enums::api_error_type::ApiError;
+use ic_stable_structures::{storable::Bound, StableBTreeMap, Storable};
+
+use crate::{
+    rate_limit::RateLimiter,
+    store::{Memory, MEMORY_MANAGER, METRICS_COUNTERS_MEMORY_ID, METRICS_HISTOGRAMS_MEMORY_ID},
+};
+
+// The amount of hourly buckets that are kept per counter
+static HOURLY_BUCKETS: usize = 24;
+
+// Upper bounds of the instruction count histogram buckets, the last bucket is +Inf
+static INSTRUCTION_BUCKETS: [u64; 5] = [100_000, 1_000_000, 10_000_000, 100_000_000, 1_000_000_000];
+
+#[derive(CandidType, Clone, Deserialize, Debug, Default)]
+pub struct Counter {
+    pub total: u64,
+    // (hour since epoch, count) of the most recent hours
+    pub hourly: Vec<(u64, u64)>,
+}
+
+impl Storable for Counter {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct Histogram {
+    // Counts per bucket of `INSTRUCTION_BUCKETS`, followed by the +Inf bucket
+    pub buckets: Vec<u64>,
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: vec![0; INSTRUCTION_BUCKETS.len() + 1],
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Storable for Histogram {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    // Counters by series (ex; `gated_join_failures{reason="NOT_OWNING_NFT"}`)
+    pub static COUNTERS: RefCell<StableBTreeMap<String, Counter, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(METRICS_COUNTERS_MEMORY_ID)),
+        )
+    );
+
+    // Instruction count histograms by update method
+    pub static HISTOGRAMS: RefCell<StableBTreeMap<String, Histogram, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(METRICS_HISTOGRAMS_MEMORY_ID)),
+        )
+    );
+}
+
+pub struct Metrics;
+
+impl Metrics {
+    // Method to increment a counter, the labels are added to the series name
+    // state changes in query calls are discarded, so this should only be used from update calls
+    pub fn increment(name: &str, labels: &[(&str, &str)]) {
+        let series = match labels.is_empty() {
+            true => name.to_string(),
+            false => format!(
+                "{}{{{}}}",
+                name,
+                labels
+                    .iter()
+                    .map(|(key, value)| format!("{}=\"{}\"", key, value))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+        };
+
+        let hour = time() / 1_000_000_000 / 3600;
+        COUNTERS.with(|c| {
+            let mut counters = c.borrow_mut();
+            let mut counter = counters.get(&series).unwrap_or_default();
+            counter.total += 1;
+
+            match counter.hourly.last_mut() {
+                Some((_hour, _count)) if *_hour == hour => *_count += 1,
+                _ => counter.hourly.push((hour, 1)),
+            }
+            if counter.hourly.len() > HOURLY_BUCKETS {
+                let excess = counter.hourly.len() - HOURLY_BUCKETS;
+                counter.hourly.drain(0..excess);
+            }
+
+            counters.insert(series, counter);
+        });
+    }
+
+    // Method to increment the failure counter for the tag of an error (ex; `NOT_OWNING_NFT`)
+    pub fn increment_error(name: &str, err: &ApiError, tags: &[&str]) {
+        if let Some(_tag) = Self::error_tag(err) {
+            if tags.contains(&_tag.as_str()) {
+                Self::increment(name, &[("reason", &_tag)]);
+            }
+        }
+    }
+
+    fn error_tag(err: &ApiError) -> Option<String> {
+        use ApiError::*;
+        match err {
+            SerializeError(_message)
+            | DeserializeError(_message)
+            | NotFound(_message)
+            | CanisterAtCapacity(_message)
+            | Unauthorized(_message)
+            | Unexpected(_message)
+            | BadRequest(_message) => Some(_message.tag.clone()),
+            _ => None,
+        }
+    }
+
+    // Method to add the instructions of the current call to the histogram of a method, called at the end of the update handlers
+    // query handlers can not record their own instructions because state changes in query calls are discarded
+    pub fn observe_instructions(method_name: &str) {
+        // Counter 1 covers the whole call context, so the instructions before an await are included
+        let instructions = performance_counter(1);
+        HISTOGRAMS.with(|h| {
+            let mut histograms = h.borrow_mut();
+            let mut histogram = histograms.get(&method_name.to_string()).unwrap_or_default();
+
+            let bucket = INSTRUCTION_BUCKETS
+                .iter()
+                .position(|bound| &instructions <= bound)
+                .unwrap_or(INSTRUCTION_BUCKETS.len());
+            histogram.buckets[bucket] += 1;
+            histogram.sum += instructions;
+            histogram.count += 1;
+
+            histograms.insert(method_name.to_string(), histogram);
+        });
+    }
+
+    // Method to render the counters and histograms in the Prometheus text format
+    pub fn render() -> String {
+        let previous_hour = time() / 1_000_000_000 / 3600 - 1;
+        let mut output = String::new();
+
+        let counters: Vec<(String, Counter)> = COUNTERS.with(|c| c.borrow().iter().collect());
+        let families: BTreeSet<&str> = counters
+            .iter()
+            .map(|(series, _)| series.split('{').next().unwrap_or_default())
+            .collect();
+
+        for family in families {
+            output.push_str(&format!("# TYPE member_{}_total counter\n", family));
+            output.push_str(&format!("# TYPE member_{}_last_hour gauge\n", family));
+            for (series, counter) in counters
+                .iter()
+                .filter(|(series, _)| series.split('{').next() == Some(family))
+            {
+                let labels = &series[family.len()..];
+                let last_hour = counter
+                    .hourly
+                    .iter()
+                    .find(|(hour, _)| hour == &previous_hour)
+                    .map(|(_, count)| *count)
+                    .unwrap_or(0);
+                output.push_str(&format!(
+                    "member_{}_total{} {}\n",
+                    family, labels, counter.total
+                ));
+                output.push_str(&format!(
+                    "member_{}_last_hour{} {}\n",
+                    family, labels, last_hour
+                ));
+            }
+        }
+
+        let histograms: Vec<(String, Histogram)> = HISTOGRAMS.with(|h| h.borrow().iter().collect());
+        if !histograms.is_empty() {
+            output.push_str("# TYPE member_update_instructions histogram\n");
+        }
+        for (method_name, histogram) in histograms {
+            let mut cumulative = 0;
+            for (index, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = match INSTRUCTION_BUCKETS.get(index) {
+                    Some(_bound) => _bound.to_string(),
+                    None => "+Inf".to_string(),
+                };
+                output.push_str(&format!(
+                    "member_update_instructions_bucket{{method=\"{}\",le=\"{}\"}} {}\n",
+                    method_name, bound, cumulative
+                ));
+            }
+            output.push_str(&format!(
+                "member_update_instructions_sum{{method=\"{}\"}} {}\n",
+                method_name, histogram.sum
+            ));
+            output.push_str(&format!(
+                "member_update_instructions_count{{method=\"{}\"}} {}\n",
+                method_name, histogram.count
+            ));
+        }
+
+        output.push_str(&RateLimiter::render_metrics());
+        output
+    }
+}
@@ -0,0 +1,334 @@
+use std::{borrow::Cow, cell::RefCell, collections::HashMap};
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::time;
+use ic_scalable_canister::{
+    ic_scalable_misc::{
+        enums::api_error_type::{ApiError, ApiErrorType},
+        helpers::error_helper::api_error,
+    },
+    store::Data,
+};
+use ic_stable_structures::{storable::Bound, StableCell, Storable};
+use shared::member_model::RateLimit;
+
+use crate::{
+    metrics::Metrics,
+    store::{Memory, Store, MEMORY_MANAGER, RATE_LIMITS_MEMORY_ID, STABLE_DATA},
+};
+
+// A bucket holds the tokens in thousandths, so partial refills are not lost between calls
+static MILLITOKENS_PER_CALL: u64 = 1_000;
+
+static NANOS_PER_MINUTE: u128 = 60_000_000_000;
+
+// The amount of buckets after which the full buckets are dropped, a full bucket is the same as no bucket
+static MAX_TRACKED_BUCKETS: usize = 10_000;
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitScope {
+    Principal,
+    Group,
+}
+
+impl RateLimitScope {
+    fn label(&self) -> &'static str {
+        match self {
+            RateLimitScope::Principal => "principal",
+            RateLimitScope::Group => "group",
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct TokenBucket {
+    millitokens: u64,
+    updated_at: u64,
+}
+
+// The buckets as they are stored on upgrade
+#[derive(CandidType, Deserialize, Default)]
+struct RateLimitSnapshot {
+    buckets: Vec<((RateLimitScope, Principal), TokenBucket)>,
+}
+
+impl Storable for RateLimitSnapshot {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    // Token buckets by scope and principal (the caller or the group), kept in heap memory because they change on every limited call
+    static BUCKETS: RefCell<HashMap<(RateLimitScope, Principal), TokenBucket>> = RefCell::new(HashMap::new());
+
+    // Snapshot of the token buckets, written on pre_upgrade and read on post_upgrade
+    static SNAPSHOT: RefCell<StableCell<RateLimitSnapshot, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(RATE_LIMITS_MEMORY_ID)),
+            RateLimitSnapshot::default(),
+        ).expect("failed")
+    );
+}
+
+// Token bucket rate limits of the membership mutations, the limits are part of the config pushed by the parent canister
+// a call takes a token from the bucket of the caller and, for calls on a group, from the bucket of the group
+pub struct RateLimiter;
+
+impl RateLimiter {
+    // Method to take a token for a call, returns a `RATE_LIMITED` error when one of the buckets is empty
+    // no token is taken from any bucket when the call is limited
+    pub fn check(
+        principal: Principal,
+        group_identifier: Option<Principal>,
+        method_name: &str,
+    ) -> Result<(), ApiError> {
+        Self::check_principals(&[principal], group_identifier, method_name)
+    }
+
+    // Method to take a token from the bucket of every principal of a call, used for calls that act for another principal
+    pub fn check_principals(
+        principals: &[Principal],
+        group_identifier: Option<Principal>,
+        method_name: &str,
+    ) -> Result<(), ApiError> {
+        let config = Store::get_config();
+        let limits = Self::get_limits(
+            principals,
+            group_identifier,
+            config.principal_rate_limit,
+            config.group_rate_limit,
+        );
+        let limited_scope = Self::take_tokens(&limits, time());
+
+        if BUCKETS.with(|b| b.borrow().len()) > MAX_TRACKED_BUCKETS {
+            Self::drop_full_buckets();
+        }
+
+        match limited_scope {
+            None => Ok(()),
+            Some(_scope) => {
+                Metrics::increment(
+                    "rate_limited",
+                    &[("method", method_name), ("scope", _scope.label())],
+                );
+                Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "RATE_LIMITED",
+                    format!(
+                        "The rate limit of the {} is reached, try again later",
+                        _scope.label()
+                    )
+                    .as_str(),
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    method_name,
+                    Some(
+                        principals
+                            .iter()
+                            .map(|principal| format!("principal - {}", principal))
+                            .collect(),
+                    ),
+                ))
+            }
+        }
+    }
+
+    // Method to get the buckets a call takes a token from, with their limit
+    fn get_limits(
+        principals: &[Principal],
+        group_identifier: Option<Principal>,
+        principal_rate_limit: Option<RateLimit>,
+        group_rate_limit: Option<RateLimit>,
+    ) -> Vec<((RateLimitScope, Principal), RateLimit)> {
+        let mut limits = vec![];
+        if let Some(_rate_limit) = principal_rate_limit {
+            for principal in principals {
+                limits.push(((RateLimitScope::Principal, *principal), _rate_limit.clone()));
+            }
+        }
+        if let (Some(_rate_limit), Some(_group_identifier)) = (group_rate_limit, group_identifier) {
+            limits.push(((RateLimitScope::Group, _group_identifier), _rate_limit));
+        }
+        limits
+    }
+
+    // Method to take a token from every bucket, returns the scope of the first empty bucket without taking any token
+    fn take_tokens(
+        limits: &[((RateLimitScope, Principal), RateLimit)],
+        now: u64,
+    ) -> Option<RateLimitScope> {
+        BUCKETS.with(|b| {
+            let mut buckets = b.borrow_mut();
+            let refilled: Vec<((RateLimitScope, Principal), TokenBucket)> = limits
+                .iter()
+                .map(|(key, rate_limit)| (*key, Self::refill(buckets.get(key), rate_limit, now)))
+                .collect();
+
+            if let Some(((_scope, _), _)) = refilled
+                .iter()
+                .find(|(_, bucket)| bucket.millitokens < MILLITOKENS_PER_CALL)
+            {
+                return Some(*_scope);
+            }
+
+            for (key, mut bucket) in refilled {
+                bucket.millitokens -= MILLITOKENS_PER_CALL;
+                buckets.insert(key, bucket);
+            }
+            None
+        })
+    }
+
+    // Method to store the buckets before an upgrade, the full buckets are left out
+    pub fn save_snapshot() {
+        Self::drop_full_buckets();
+        let buckets = BUCKETS.with(|b| {
+            b.borrow()
+                .iter()
+                .map(|(key, bucket)| (*key, bucket.clone()))
+                .collect()
+        });
+        let _ = SNAPSHOT.with(|s| s.borrow_mut().set(RateLimitSnapshot { buckets }));
+    }
+
+    // Method to restore the buckets after an upgrade
+    pub fn restore_snapshot() {
+        let snapshot = SNAPSHOT.with(|s| s.borrow_mut().set(RateLimitSnapshot::default()));
+        if let Ok(_snapshot) = snapshot {
+            BUCKETS.with(|b| *b.borrow_mut() = _snapshot.buckets.into_iter().collect());
+        }
+    }
+
+    // Method to render the configured limits and the tracked buckets in the Prometheus text format
+    pub fn render_metrics() -> String {
+        let config = Store::get_config();
+        let limits: Vec<(RateLimitScope, RateLimit)> = vec![
+            (RateLimitScope::Principal, config.principal_rate_limit),
+            (RateLimitScope::Group, config.group_rate_limit),
+        ]
+        .into_iter()
+        .filter_map(|(scope, rate_limit)| rate_limit.map(|_rate_limit| (scope, _rate_limit)))
+        .collect();
+
+        if limits.is_empty() {
+            return String::new();
+        }
+
+        let mut output = String::new();
+        output.push_str("# TYPE member_rate_limit_capacity gauge\n");
+        output.push_str("# TYPE member_rate_limit_refill_per_minute gauge\n");
+        output.push_str("# TYPE member_rate_limit_buckets gauge\n");
+        for (scope, rate_limit) in limits {
+            let buckets = BUCKETS.with(|b| b.borrow().keys().filter(|key| key.0 == scope).count());
+            output.push_str(&format!(
+                "member_rate_limit_capacity{{scope=\"{}\"}} {}\n",
+                scope.label(),
+                rate_limit.capacity
+            ));
+            output.push_str(&format!(
+                "member_rate_limit_refill_per_minute{{scope=\"{}\"}} {}\n",
+                scope.label(),
+                rate_limit.refill_per_minute
+            ));
+            output.push_str(&format!(
+                "member_rate_limit_buckets{{scope=\"{}\"}} {}\n",
+                scope.label(),
+                buckets
+            ));
+        }
+        output
+    }
+
+    // Method to get a bucket with the tokens that are refilled since the last call, a new bucket starts full
+    fn refill(bucket: Option<&TokenBucket>, rate_limit: &RateLimit, now: u64) -> TokenBucket {
+        let max_millitokens = rate_limit.capacity.saturating_mul(MILLITOKENS_PER_CALL);
+
+        match bucket {
+            None => TokenBucket {
+                millitokens: max_millitokens,
+                updated_at: now,
+            },
+            Some(_bucket) => {
+                let elapsed = now.saturating_sub(_bucket.updated_at) as u128;
+                let refilled =
+                    elapsed * rate_limit.refill_per_minute as u128 * MILLITOKENS_PER_CALL as u128
+                        / NANOS_PER_MINUTE;
+
+                TokenBucket {
+                    millitokens: (_bucket.millitokens as u128 + refilled)
+                        .min(max_millitokens as u128) as u64,
+                    updated_at: now,
+                }
+            }
+        }
+    }
+
+    // Method to drop the buckets that are refilled completely, or of which the limit is removed
+    fn drop_full_buckets() {
+        let config = Store::get_config();
+        let now = time();
+
+        BUCKETS.with(|b| {
+            b.borrow_mut().retain(|(scope, _), bucket| {
+                let rate_limit = match scope {
+                    RateLimitScope::Principal => &config.principal_rate_limit,
+                    RateLimitScope::Group => &config.group_rate_limit,
+                };
+
+                match rate_limit {
+                    None => false,
+                    Some(_rate_limit) => {
+                        Self::refill(Some(bucket), _rate_limit, now).millitokens
+                            < _rate_limit.capacity.saturating_mul(MILLITOKENS_PER_CALL)
+                    }
+                }
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_calling_canister() {
+        let rate_limit = RateLimit {
+            capacity: 3,
+            refill_per_minute: 0,
+        };
+        let canister = Principal::from_slice(&[1]);
+
+        // The same canister creates members for a different user on every call
+        let results: Vec<Option<RateLimitScope>> = (0..5u8)
+            .map(|user| {
+                let limits = RateLimiter::get_limits(
+                    &[canister, Principal::from_slice(&[2, user])],
+                    None,
+                    Some(rate_limit.clone()),
+                    None,
+                );
+                RateLimiter::take_tokens(&limits, 0)
+            })
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                None,
+                None,
+                None,
+                Some(RateLimitScope::Principal),
+                Some(RateLimitScope::Principal)
+            ]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
+
+use candid::Principal;
+use ic_cdk_timers::set_timer;
+use ic_scalable_canister::ic_scalable_misc::helpers::serialize_helper::serialize;
+use shared::chunk_model::{checksum, ChunkCodec};
+
+use crate::store::Store;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SnapshotKind {
+    Members,
+    Invites,
+}
+
+// The maximum amount of bytes of all cached snapshots together, the oldest snapshots are evicted first
+static MAX_SNAPSHOT_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+// The serialized data of a group, as served by the chunk calls
+struct Snapshot {
+    // The mutation version of the group the snapshot is built for
+    version: u64,
+    // Incremented for every snapshot that is built, used to evict the oldest snapshots
+    sequence: u64,
+    identity: EncodedSnapshot,
+    gzip: EncodedSnapshot,
+}
+
+impl Snapshot {
+    fn size(&self) -> usize {
+        self.identity.bytes.len() + self.gzip.bytes.len()
+    }
+}
+
+struct EncodedSnapshot {
+    bytes: Vec<u8>,
+    // The `checksum` of the bytes, served as the snapshot id of the chunks
+    hash: Vec<u8>,
+}
+
+impl EncodedSnapshot {
+    fn new(bytes: Vec<u8>) -> Self {
+        EncodedSnapshot {
+            hash: checksum(&bytes),
+            bytes,
+        }
+    }
+}
+
+thread_local! {
+    // Mutation version per group, bumped on every join, leave, role or invite write to the group
+    static GROUP_VERSIONS: RefCell<HashMap<Principal, u64>> = RefCell::new(HashMap::new());
+
+    // Serialized snapshots per group and kind, not stored because they can be rebuilt from the member entries
+    static SNAPSHOTS: RefCell<HashMap<(Principal, SnapshotKind), Snapshot>> = RefCell::new(HashMap::new());
+
+    // The snapshots in the order they are built, used to evict the oldest snapshots
+    // entries of which the sequence does not match the cached snapshot anymore are skipped
+    static SNAPSHOT_ORDER: RefCell<VecDeque<((Principal, SnapshotKind), u64)>> = const { RefCell::new(VecDeque::new()) };
+
+    // The amount of bytes of all cached snapshots together
+    static CACHED_BYTES: Cell<usize> = const { Cell::new(0) };
+
+    static NEXT_SEQUENCE: Cell<u64> = const { Cell::new(0) };
+
+    // Groups of which the snapshots are rebuilt by the next timer run
+    static PENDING_GROUPS: RefCell<HashSet<Principal>> = RefCell::new(HashSet::new());
+}
+
+// Cache of the serialized group snapshots that are served by the chunk calls
+// state changes in query calls are discarded, so the snapshots are built by a timer after every write to a group
+// groups that fit in a single chunk are not cached, these are serialized once per call anyway
+// the cache is capped at `MAX_SNAPSHOT_CACHE_BYTES`, the oldest built snapshots are evicted first
+// because query calls can not keep track of the recently used snapshots (their state changes are discarded)
+pub struct SnapshotCache;
+
+impl SnapshotCache {
+    // Method to invalidate the snapshots of groups after a write, the snapshots are rebuilt by a timer
+    pub fn invalidate(group_identifiers: Vec<Principal>) {
+        if group_identifiers.is_empty() {
+            return;
+        }
+
+        let schedule = PENDING_GROUPS.with(|p| p.borrow().is_empty());
+
+        for group_identifier in group_identifiers {
+            GROUP_VERSIONS.with(|v| *v.borrow_mut().entry(group_identifier).or_insert(0) += 1);
+            Self::remove((group_identifier, SnapshotKind::Members));
+            Self::remove((group_identifier, SnapshotKind::Invites));
+            PENDING_GROUPS.with(|p| p.borrow_mut().insert(group_identifier));
+        }
+
+        if schedule {
+            set_timer(Duration::ZERO, Self::rebuild_pending);
+        }
+    }
+
+    // Method to drop all snapshots, used when the member entries are replaced
+    pub fn clear() {
+        SNAPSHOTS.with(|s| s.borrow_mut().clear());
+        SNAPSHOT_ORDER.with(|o| o.borrow_mut().clear());
+        CACHED_BYTES.with(|c| c.set(0));
+        PENDING_GROUPS.with(|p| p.borrow_mut().clear());
+    }
+
+    // Method to read the snapshot of a group with its hash, returns `None` when there is no up-to-date snapshot
+    pub fn with_snapshot<R>(
+        group_identifier: Principal,
+        kind: SnapshotKind,
+        codec: ChunkCodec,
+        f: impl FnOnce(&[u8], &[u8]) -> R,
+    ) -> Option<R> {
+        let version = Self::get_version(group_identifier);
+
+        SNAPSHOTS.with(|s| {
+            s.borrow()
+                .get(&(group_identifier, kind))
+                .filter(|snapshot| snapshot.version == version)
+                .map(|snapshot| match codec {
+                    ChunkCodec::Identity => f(&snapshot.identity.bytes, &snapshot.identity.hash),
+                    ChunkCodec::Gzip => f(&snapshot.gzip.bytes, &snapshot.gzip.hash),
+                })
+        })
+    }
+
+    fn get_version(group_identifier: Principal) -> u64 {
+        GROUP_VERSIONS.with(|v| v.borrow().get(&group_identifier).copied().unwrap_or(0))
+    }
+
+    fn rebuild_pending() {
+        let group_identifiers: Vec<Principal> =
+            PENDING_GROUPS.with(|p| p.borrow_mut().drain().collect());
+        let max_bytes_per_chunk = Store::get_config().max_bytes_per_chunk as usize;
+
+        for group_identifier in group_identifiers {
+            let version = Self::get_version(group_identifier);
+
+            let members = serialize(&Store::get_group_members(group_identifier));
+            let invites = serialize(&Store::get_group_invites(group_identifier));
+
+            for (kind, serialized) in [
+                (SnapshotKind::Members, members),
+                (SnapshotKind::Invites, invites),
+            ] {
+                let identity = match serialized {
+                    Ok(_bytes) if _bytes.len() > max_bytes_per_chunk => _bytes,
+                    _ => continue,
+                };
+                let gzip = match ChunkCodec::Gzip.encode(identity.clone()) {
+                    Ok(_bytes) => _bytes,
+                    Err(_) => continue,
+                };
+
+                Self::insert(
+                    (group_identifier, kind),
+                    version,
+                    EncodedSnapshot::new(identity),
+                    EncodedSnapshot::new(gzip),
+                );
+            }
+        }
+    }
+
+    // Method to cache a snapshot, the oldest snapshots are evicted when the cache exceeds `MAX_SNAPSHOT_CACHE_BYTES`
+    fn insert(
+        key: (Principal, SnapshotKind),
+        version: u64,
+        identity: EncodedSnapshot,
+        gzip: EncodedSnapshot,
+    ) {
+        Self::remove(key);
+
+        let sequence = NEXT_SEQUENCE.with(|n| {
+            let sequence = n.get();
+            n.set(sequence + 1);
+            sequence
+        });
+        let snapshot = Snapshot {
+            version,
+            sequence,
+            identity,
+            gzip,
+        };
+
+        let size = snapshot.size();
+        if size > MAX_SNAPSHOT_CACHE_BYTES {
+            return;
+        }
+
+        while CACHED_BYTES.with(|c| c.get()) + size > MAX_SNAPSHOT_CACHE_BYTES {
+            let oldest = match SNAPSHOT_ORDER.with(|o| o.borrow_mut().pop_front()) {
+                Some(_oldest) => _oldest,
+                None => break,
+            };
+            let is_current = SNAPSHOTS.with(|s| {
+                s.borrow()
+                    .get(&oldest.0)
+                    .is_some_and(|snapshot| snapshot.sequence == oldest.1)
+            });
+            if is_current {
+                Self::remove(oldest.0);
+            }
+        }
+
+        CACHED_BYTES.with(|c| c.set(c.get() + size));
+        SNAPSHOT_ORDER.with(|o| o.borrow_mut().push_back((key, sequence)));
+        SNAPSHOTS.with(|s| s.borrow_mut().insert(key, snapshot));
+    }
+
+    fn remove(key: (Principal, SnapshotKind)) {
+        if let Some(_snapshot) = SNAPSHOTS.with(|s| s.borrow_mut().remove(&key)) {
+            CACHED_BYTES.with(|c| c.set(c.get().saturating_sub(_snapshot.size())));
+        }
+    }
+}
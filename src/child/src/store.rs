@@ -1,10 +1,11 @@
-use std::{cell::RefCell, collections::HashMap, iter::FromIterator, vec};
+use std::{cell::RefCell, collections::HashMap, iter::FromIterator, time::Duration, vec};
 
-use candid::Principal;
+use candid::{utils::ArgumentEncoder, CandidType, Principal};
 use ic_cdk::{
     api::{self, call, time},
     id,
 };
+use ic_cdk_timers::set_timer;
 use ic_scalable_canister::ic_scalable_misc::{
     enums::{
         api_error_type::{ApiError, ApiErrorType},
@@ -12,22 +13,33 @@ use ic_scalable_canister::ic_scalable_misc::{
     },
     helpers::{
         error_helper::api_error,
-        role_helper::{default_roles, get_group_roles, has_permission},
-        serialize_helper::serialize,
+        logger_helper::add_log,
+        role_helper::{default_roles, get_group_roles},
+        serialize_helper::{deserialize, serialize},
         token_canister_helper::{
             dip20_balance_of, dip721_balance_of, ext_balance_of, legacy_dip721_balance_of,
         },
     },
     models::{
+        group_role::GroupRole,
         identifier_model::Identifier,
+        logger_models::{LogType, PostLog},
         neuron_models::{DissolveState, ListNeurons, ListNeuronsResponse},
-        permissions_models::{PermissionActionType, PermissionType},
+        permissions_models::{Permission, PermissionActionType, PermissionActions, PermissionType},
     },
 };
 use ic_scalable_canister::store::Data;
 
-use shared::member_model::{
-    Invite, InviteMemberResponse, InviteType, Join, JoinedMemberResponse, Member,
+use serde::de::DeserializeOwned;
+use shared::{
+    capacity_model::{GroupCapacity, SeatClaim},
+    chunk_model::{checksum, ChunkCodec, ChunkError, ChunkRequest, DataChunk},
+    invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest},
+    member_model::{
+        ChildConfig, Invite, InviteMemberResponse, InviteType, Join, JoinAnswer, JoinQuestion,
+        JoinedMemberResponse, Member, MemberEntryResponse, MemberSearchResult, MemberStats,
+        MemberStatus, ProfileFieldDefinition, MODERATION_PERMISSION,
+    },
 };
 
 use ic_stable_structures::{
@@ -35,12 +47,38 @@ use ic_stable_structures::{
     {DefaultMemoryImpl, StableBTreeMap, StableCell},
 };
 
-use crate::IDENTIFIER_KIND;
+use crate::{
+    certification::Certification,
+    metrics::Metrics,
+    snapshot::{SnapshotCache, SnapshotKind},
+    IDENTIFIER_KIND,
+};
 
-type Memory = VirtualMemory<DefaultMemoryImpl>;
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 pub static DATA_MEMORY_ID: MemoryId = MemoryId::new(0);
 pub static ENTRIES_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub static CONFIG_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub static METRICS_COUNTERS_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub static METRICS_HISTOGRAMS_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub static DISPLAY_NAMES_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub static RATE_LIMITS_MEMORY_ID: MemoryId = MemoryId::new(6);
+
+// The maximum amount of results a member search returns
+pub static MAX_SEARCH_RESULTS: usize = 100;
+
+// The maximum length of a display name that is pushed by a profile canister
+pub static MAX_DISPLAY_NAME_LENGTH: usize = 64;
+
+// The maximum amount of per-group profile fields of a member, and the maximum length of a field value
+pub static MAX_PROFILE_FIELDS: usize = 20;
+pub static MAX_PROFILE_FIELD_LENGTH: usize = 1024;
+
+// The maximum length of an answer to a question of a join questionnaire
+pub static MAX_JOIN_ANSWER_LENGTH: usize = 2000;
+
+// The amount of attempts to release a seat of a group on the parent canister
+static RELEASE_SEAT_ATTEMPTS: usize = 3;
 thread_local! {
     pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
@@ -59,6 +97,21 @@ thread_local! {
                 MEMORY_MANAGER.with(|m| m.borrow().get(ENTRIES_MEMORY_ID)),
             )
         );
+
+        // Configuration pushed by the parent canister
+        pub static CONFIG: RefCell<StableCell<ChildConfig, Memory>> = RefCell::new(
+            StableCell::init(
+                MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEMORY_ID)),
+                ChildConfig::default(),
+            ).expect("failed")
+        );
+
+        // Display names by member principal, pushed by the profile canister of the member and used for the member search
+        pub static DISPLAY_NAMES: RefCell<StableBTreeMap<String, String, Memory>> = RefCell::new(
+            StableBTreeMap::init(
+                MEMORY_MANAGER.with(|m| m.borrow().get(DISPLAY_NAMES_MEMORY_ID)),
+            )
+        );
 }
 
 pub struct Store;
@@ -69,7 +122,8 @@ impl Store {
         caller: Principal,
         group_identifier: Principal,
         account_identifier: Option<String>,
-    ) -> Result<(Principal, Member), ApiError> {
+        answers: Option<Vec<JoinAnswer>>,
+    ) -> Result<MemberEntryResponse, ApiError> {
         // Get the group owner and privacy from an inter-canister call
         let group_owner_and_privacy: Result<(Principal, Privacy), ApiError> =
             Self::get_group_owner_and_privacy(group_identifier.clone()).await;
@@ -137,71 +191,102 @@ impl Store {
                 let updated_member = Self::add_invite_or_join_group_to_member(
                     caller,
                     group_identifier,
-                    existing_member.clone(),
+                    existing_member,
                     _group_privacy,
                     account_identifier,
+                    answers,
                 )
                 .await;
 
-                // update the member
-                match updated_member {
-                    // if the call fails return an error
-                    Err(err) => Err(err),
-                    // if the call succeeds, continue
-                    Ok(_updated_member) => match existing_member {
-                        None => {
-                            // if there is no existing member, add a new one
-                            let result = STABLE_DATA.with(|data| {
-                                ENTRIES.with(|entries| {
-                                    Data::add_entry(
-                                        data,
-                                        entries,
-                                        _updated_member.clone(),
-                                        Some(IDENTIFIER_KIND.to_string()),
-                                    )
-                                })
-                            });
-                            // fire and forget inter canister call to update the group member count on the group canister
-                            ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
-                            match result {
-                                // The group was not added to the data store because the canister is at capacity
-                                Err(err) => match err {
-                                    ApiError::CanisterAtCapacity(message) => {
-                                        let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
-                                        // Spawn a sibling canister and pass the group data to it
-                                        match Data::spawn_sibling(&_data, _updated_member).await {
-                                            Ok(_) => Err(ApiError::CanisterAtCapacity(message)),
-                                            Err(err) => Err(err),
-                                        }
-                                    }
-                                    _ => Err(err),
-                                },
-                                Ok((_identifier, _member_data)) => Ok((_identifier, _member_data)),
+                // if the call fails return an error
+                let updated_member = updated_member?;
+
+                // claim a seat when the group is joined directly, past the capacity of the group the join is waitlisted
+                let seat_claimed = updated_member.joined.contains_key(&group_identifier);
+                if seat_claimed {
+                    Self::claim_group_seat(group_identifier, caller, "member".to_string()).await?;
+                }
+
+                // the member is read again as it can be changed during the inter-canister calls
+                let result = match Self::_get_member_from_caller(caller) {
+                    // if there is no existing member, add a new one (or hand it over to a new sibling canister)
+                    None => Self::add_member_entry(updated_member).await,
+                    // if there is an existing member, add the join or invite to the current member
+                    Some((_identifier, mut _member)) => {
+                        match Self::get_join_conflict(&_member, group_identifier) {
+                            Some((_tag, _message)) => Err(api_error(
+                                ApiErrorType::BadRequest,
+                                _tag,
+                                _message,
+                                STABLE_DATA
+                                    .with(|data| Data::get_name(data.borrow().get()))
+                                    .as_str(),
+                                "join_group",
+                                None,
+                            )),
+                            None => {
+                                if let Some(_join) = updated_member.joined.get(&group_identifier) {
+                                    _member.joined.insert(group_identifier, _join.clone());
+                                }
+                                if let Some(_invite) = updated_member.invites.get(&group_identifier)
+                                {
+                                    _member.invites.insert(group_identifier, _invite.clone());
+                                }
+
+                                // update the member
+                                STABLE_DATA
+                                    .with(|data| {
+                                        ENTRIES.with(|entries| {
+                                            Self::update_entry(data, entries, _identifier, _member)
+                                        })
+                                    })
+                                    .map(|(_identifier, _member)| MemberEntryResponse {
+                                        canister: id(),
+                                        identifier: Some(_identifier),
+                                        member: _member,
+                                    })
                             }
                         }
-                        // if there is an existing member, update the existing one
-                        Some((_identifier, _)) => {
-                            // update the member
-                            let result = STABLE_DATA.with(|data| {
-                                ENTRIES.with(|entries| {
-                                    Data::update_entry(data, entries, _identifier, _updated_member)
-                                })
-                            });
-                            // fire and forget inter canister call to update the group member count on the group canister
-                            ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
-                            result
-                        }
-                    },
+                    }
+                };
+
+                if seat_claimed && result.is_err() {
+                    Self::release_group_seat(group_identifier).await;
                 }
+
+                // fire and forget inter canister call to update the group member count on the group canister
+                ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+                result
             }
         }
     }
 
+    // Method to check if the calling canister is the profile canister that stores the profile
+    pub fn check_profile_canister(
+        calling_canister: Principal,
+        profile_identifier: Principal,
+    ) -> Result<(), ApiError> {
+        let (_, profile_canister, _) = Identifier::decode(&profile_identifier);
+        match calling_canister == profile_canister {
+            true => Ok(()),
+            false => Err(api_error(
+                ApiErrorType::Unauthorized,
+                "NOT_PROFILE_CANISTER",
+                "Only the canister that stores the profile can create a member",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "create_empty_member",
+                Some(vec![format!("profile_identifier - {}", profile_identifier)]),
+            )),
+        }
+    }
+
     // Method to create an empty member
-    pub fn create_empty_member(
+    pub async fn create_empty_member(
         caller: Principal,
         profile_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
+    ) -> Result<MemberEntryResponse, ApiError> {
         // Decode the profile identifier
         let (_, _, kind) = Identifier::decode(&profile_identifier);
 
@@ -228,21 +313,8 @@ impl Store {
                         joined: HashMap::new(),
                         invites: HashMap::new(),
                     };
-                    // Add the new member
-                    let result = STABLE_DATA.with(|data| {
-                        ENTRIES.with(|entries| {
-                            Data::add_entry(
-                                data,
-                                entries,
-                                empty_member,
-                                Some(IDENTIFIER_KIND.to_string()),
-                            )
-                        })
-                    });
-                    match result {
-                        Ok((_identfier, _)) => Ok(_identfier),
-                        Err(err) => Err(err),
-                    }
+                    // Add the new member (or hand it over to a new sibling canister)
+                    Self::add_member_entry(empty_member).await
                 }
                 // If there is an existing member, throw an error
                 Some(_) => Err(api_error(
@@ -270,11 +342,15 @@ impl Store {
 
             // If there is an existing member, continue
             Some((_identifier, mut _member)) => {
-                _member.joined.remove(&group_identifier);
+                let join = _member.joined.remove(&group_identifier);
                 let _ = STABLE_DATA.with(|data| {
-                    ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
+                    ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
                 });
                 ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+                // Release the seat of the member so the first member on the waitlist is promoted
+                if join.is_some() {
+                    ic_cdk::spawn(Self::release_group_seat(group_identifier));
+                }
                 Ok(())
             }
         }
@@ -291,8 +367,10 @@ impl Store {
             Some((_identifier, mut _member)) => {
                 _member.invites.remove(&group_identifier);
                 let _ = STABLE_DATA.with(|data| {
-                    ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
+                    ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
                 });
+                // An accepted invite can be waitlisted, the member is not promoted after the invite is removed
+                ic_cdk::spawn(Self::drop_waitlist_entry(group_identifier, caller));
                 Ok(())
             }
         }
@@ -326,12 +404,14 @@ impl Store {
                             roles: vec![role.clone()],
                             updated_at: time(),
                             created_at: time(),
+                            status: None,
+                            profile: None,
                         };
                         _member.joined.insert(group_identifier, join);
                     }
                 }
                 let _ = STABLE_DATA.with(|data| {
-                    ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
+                    ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
                 });
             }
             Ok(())
@@ -360,13 +440,15 @@ impl Store {
                                 roles,
                                 updated_at: time(),
                                 created_at: time(),
+                                status: None,
+                                profile: None,
                             };
                             _member.joined.insert(group_identifier, join);
                         }
                     }
                     let _ = STABLE_DATA.with(|data| {
                         ENTRIES.with(|entries| {
-                            Data::update_entry(data, entries, member_identifier, _member)
+                            Self::update_entry(data, entries, member_identifier, _member)
                         })
                     });
                 }
@@ -404,7 +486,7 @@ impl Store {
             }
 
             let _ = STABLE_DATA.with(|data| {
-                ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
+                ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
             });
             Ok(())
         } else {
@@ -431,13 +513,22 @@ impl Store {
                         None,
                     )),
                     Some((_identifier, mut _member)) => {
-                        _member.joined.remove(&group_identifier);
+                        let join = _member.joined.remove(&group_identifier);
                         let _ = STABLE_DATA.with(|data| {
                             ENTRIES.with(|entries| {
-                                Data::update_entry(data, entries, _identifier, _member)
+                                Self::update_entry(data, entries, _identifier, _member)
                             })
                         });
                         ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+                        // Release the seat of the member so the first member on the waitlist is promoted
+                        if join.is_some() {
+                            ic_cdk::spawn(Self::release_group_seat(group_identifier));
+                        }
+                        // A removed member that is on the waitlist is not promoted anymore
+                        ic_cdk::spawn(Self::drop_waitlist_entry(
+                            group_identifier,
+                            member_principal,
+                        ));
                         Ok(())
                     }
                 }
@@ -479,13 +570,32 @@ impl Store {
             Some((_identifier, mut _member)) => {
                 _member.invites.remove(&group_identifier);
                 let _ = STABLE_DATA.with(|data| {
-                    ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
+                    ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
                 });
+                // An accepted invite can be waitlisted, the member is not promoted after the invite is removed
+                ic_cdk::spawn(Self::drop_waitlist_entry(group_identifier, caller));
                 Ok(())
             }
         }
     }
 
+    // Method to check if a member already joined or has a pending invite for a group, returns the tag and message of the error
+    fn get_join_conflict(
+        member: &Member,
+        group_identifier: Principal,
+    ) -> Option<(&'static str, &'static str)> {
+        if member.joined.contains_key(&group_identifier) {
+            return Some(("ALREADY_JOINED", "You are already part of this group"));
+        }
+        if member.invites.contains_key(&group_identifier) {
+            return Some((
+                "PENDING_INVITE",
+                "There is already a pending invite for this group",
+            ));
+        }
+        None
+    }
+
     // Method to add a join or invite to a member
     async fn add_invite_or_join_group_to_member(
         caller: Principal,
@@ -494,12 +604,16 @@ impl Store {
         group_privacy: Privacy,
         // is used for NFT gated groups
         account_identifier: Option<String>,
+        // is used for private groups
+        answers: Option<Vec<JoinAnswer>>,
     ) -> Result<Member, ApiError> {
         // Create a join entry based on the group privacy settings and set the default role
         let join = Join {
             roles: vec!["member".to_string()],
             updated_at: time(),
             created_at: time(),
+            status: None,
+            profile: None,
         };
 
         // Create an invite entry based on the group privacy settings
@@ -507,6 +621,7 @@ impl Store {
             invite_type: InviteType::UserRequest,
             updated_at: time(),
             created_at: time(),
+            answers: None,
         };
 
         use Privacy::*;
@@ -526,27 +641,34 @@ impl Store {
                     Ok(_member)
                 }
             },
-            // If the group is private, add the invite to the member
-            Private => match member {
-                // If the member does not exist, create a new member
-                None => Ok(Member {
-                    principal: caller,
-                    profile_identifier: Principal::anonymous(),
-                    joined: HashMap::new(),
-                    invites: HashMap::from_iter(vec![(group_identifier, invite)]),
-                }),
-                // If the member exists, add the invite to the member
-                Some((_, mut _member)) => {
-                    _member.invites.insert(group_identifier, invite);
-                    Ok(_member)
+            // If the group is private, add the invite with the answers to the join questionnaire to the member
+            Private => {
+                let invite = Invite {
+                    answers: Self::validate_join_answers(group_identifier, answers).await?,
+                    ..invite
+                };
+
+                match member {
+                    // If the member does not exist, create a new member
+                    None => Ok(Member {
+                        principal: caller,
+                        profile_identifier: Principal::anonymous(),
+                        joined: HashMap::new(),
+                        invites: HashMap::from_iter(vec![(group_identifier, invite)]),
+                    }),
+                    // If the member exists, add the invite to the member
+                    Some((_, mut _member)) => {
+                        _member.invites.insert(group_identifier, invite);
+                        Ok(_member)
+                    }
                 }
-            },
+            }
             // If the group is invite only, throw an error
             InviteOnly => {
                 return Err(api_error(
                     ApiErrorType::BadRequest,
                     "UNSUPPORTED",
-                    "This type of invite isnt supported through this call",
+                    "Invite only groups can only be joined with an invite or an invite code",
                     STABLE_DATA
                         .with(|data| Data::get_name(data.borrow().get()))
                         .as_str(),
@@ -644,6 +766,91 @@ impl Store {
         }
     }
 
+    // Method to validate the answers against the join questionnaire of the group, empty answers are dropped
+    // returns `None` when there are no answers to store on the invite
+    async fn validate_join_answers(
+        group_identifier: Principal,
+        answers: Option<Vec<JoinAnswer>>,
+    ) -> Result<Option<Vec<JoinAnswer>>, ApiError> {
+        // Get the join questionnaire of the group (inter-canister call)
+        let questions = Self::get_join_questionnaire(group_identifier).await?;
+
+        let answers: Vec<JoinAnswer> = answers
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|_answer| !_answer.answer.trim().is_empty())
+            .collect();
+
+        if let Some((_tag, _message)) = Self::get_join_answers_error(&questions, &answers) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                _tag,
+                _message.as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "validate_join_answers",
+                None,
+            ));
+        }
+
+        match answers.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(answers)),
+        }
+    }
+
+    // Method to check the answers against the questions, returns the tag and message of the first validation error
+    fn get_join_answers_error(
+        questions: &[JoinQuestion],
+        answers: &[JoinAnswer],
+    ) -> Option<(&'static str, String)> {
+        for (index, answer) in answers.iter().enumerate() {
+            let question = match questions.iter().find(|_q| _q.id == answer.question_id) {
+                None => {
+                    return Some((
+                        "INVALID_ANSWER",
+                        format!(
+                            "The question '{}' is not part of the questionnaire",
+                            answer.question_id
+                        ),
+                    ))
+                }
+                Some(_question) => _question,
+            };
+
+            let length = answer.answer.chars().count();
+            if length as u64 > question.max_length || length > MAX_JOIN_ANSWER_LENGTH {
+                return Some((
+                    "INVALID_ANSWER",
+                    format!("The answer to the question '{}' is too long", question.id),
+                ));
+            }
+
+            if answers[..index]
+                .iter()
+                .any(|_answer| _answer.question_id == answer.question_id)
+            {
+                return Some((
+                    "INVALID_ANSWER",
+                    format!("The question '{}' is answered more than once", question.id),
+                ));
+            }
+        }
+
+        questions
+            .iter()
+            .find(|_question| {
+                _question.required && !answers.iter().any(|_a| _a.question_id == _question.id)
+            })
+            .map(|_question| {
+                (
+                    "MISSING_REQUIRED_ANSWER",
+                    format!("The question '{}' is required", _question.id),
+                )
+            })
+    }
+
     // Method to check if the caller owns a specific NFT
     pub async fn validate_nft_gated(
         principal: Principal,
@@ -883,6 +1090,8 @@ impl Store {
                             member_identifier: _identifier,
                             principal: caller,
                             roles: _join.roles.clone(),
+                            status: Some(_join.get_status(time())),
+                            profile: Some(_join.profile.clone().unwrap_or_default()),
                         }),
                     }
                 }
@@ -1007,156 +1216,708 @@ impl Store {
         })
     }
 
-    // Method that is called when a group is created
-    pub async fn add_owner(
-        owner_principal: Principal,
+    // Method to search the members of a group by principal, profile identifier or display name
+    // the results are ranked by match quality (exact before prefix matches), ties are ordered by principal
+    pub fn search_group_members(
         group_identifier: Principal,
-    ) -> Result<Principal, ApiError> {
-        // Get the group owner and privacy from an inter-canister call
-        let group_owner_and_privacy =
-            Self::get_group_owner_and_privacy(group_identifier.clone()).await;
-
-        STABLE_DATA.with(|data| {
-            match group_owner_and_privacy {
-                // if the call fails return an error
-                Err(err) => Err(err),
-                Ok((_group_owner, _group_privacy)) => {
-                    // Check if the caller is the owner of the group
-                    if _group_owner != owner_principal {
-                        return Err(api_error(
-                            ApiErrorType::BadRequest,
-                            "CANT_SET_OWNER",
-                            "You are not the owner of this group",
-                            Data::get_name(data.borrow().get()).as_str(),
-                            "add_owner",
-                            None,
-                        ));
-                    }
-                    // iterate over the members and get the existing member
-                    let existing_member = Self::_get_member_from_caller(owner_principal);
-
-                    match existing_member {
-                        // If there is no exisiting member, do nothing
-                        None => {
-                            let new_member = Member {
-                                principal: owner_principal,
-                                profile_identifier: Principal::anonymous(),
-                                joined: HashMap::from_iter(vec![(
-                                    group_identifier,
-                                    Join {
-                                        roles: vec!["owner".to_string()],
-                                        updated_at: time(),
-                                        created_at: time(),
-                                    },
-                                )]),
-                                invites: HashMap::new(),
-                            };
-
-                            let response = ENTRIES.with(|entries| {
-                                Data::add_entry(
-                                    data,
-                                    entries,
-                                    new_member,
-                                    Some(IDENTIFIER_KIND.to_string()),
-                                )
-                            });
-                            match response {
-                                Err(err) => Err(err),
-                                Ok((_identifier, _member)) => Ok(_identifier),
-                            }
-                        }
-                        Some((_identifier, mut _member)) => {
-                            // if the group identifier is already found in the joined array, throw an error
-                            if _member.joined.get(&group_identifier).is_some() {
-                                return Err(api_error(
-                                    ApiErrorType::BadRequest,
-                                    "ALREADY_JOINED",
-                                    "You are already part of this group",
-                                    Data::get_name(data.borrow().get()).as_str(),
-                                    "add_owner",
-                                    None,
-                                ));
-                            }
+        query: String,
+        limit: usize,
+    ) -> Vec<MemberSearchResult> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return vec![];
+        }
 
-                            // Add the group identifier to the joined array
-                            _member.joined.insert(
+        let mut results: Vec<MemberSearchResult> = ENTRIES.with(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .filter(|(_, _member)| _member.joined.contains_key(&group_identifier))
+                .filter_map(|(_identifier, _member)| {
+                    let display_name = DISPLAY_NAMES
+                        .with(|names| names.borrow().get(&_member.principal.to_string()));
+
+                    Self::get_search_score(&query, &_member, display_name).map(|score| {
+                        MemberSearchResult {
+                            member: Self::map_member_to_joined_member_response(
+                                &Principal::from_text(_identifier)
+                                    .unwrap_or(Principal::anonymous()),
+                                &_member,
                                 group_identifier,
-                                Join {
-                                    roles: vec!["owner".to_string()],
-                                    updated_at: time(),
-                                    created_at: time(),
-                                },
-                            );
-
-                            let response = ENTRIES.with(|entries| {
-                                Data::update_entry(data, entries, _identifier, _member)
-                            });
-                            match response {
-                                Err(err) => Err(err),
-                                Ok((_identifier, _member)) => Ok(_identifier),
-                            }
+                            ),
+                            score,
                         }
-                    }
-                }
-            }
-        })
+                    })
+                })
+                .collect()
+        });
+
+        results.sort_by(|a, b| {
+            b.score.cmp(&a.score).then_with(|| {
+                a.member
+                    .principal
+                    .to_string()
+                    .cmp(&b.member.principal.to_string())
+            })
+        });
+        results.truncate(limit.min(MAX_SEARCH_RESULTS));
+        results
     }
 
-    // Method to invite a member to a group
-    pub fn invite_to_group(
-        group_identifier: Principal,
-        member_principal: Principal,
-    ) -> Result<(Principal, Member), ApiError> {
-        STABLE_DATA.with(|data| {
-            // Get the existing member
-            let existing_member = Self::_get_member_from_caller(member_principal);
+    // Method to score how well a member matches a (lowercase) search query, `None` when it does not match
+    fn get_search_score(query: &str, member: &Member, display_name: Option<String>) -> Option<u32> {
+        let principal = member.principal.to_string();
+        let profile_identifier = member.profile_identifier.to_string();
+        let display_name = display_name.unwrap_or_default().to_lowercase();
+
+        let scores = [
+            (principal == query, 100),
+            (profile_identifier == query, 90),
+            (!display_name.is_empty() && display_name == query, 80),
+            (principal.starts_with(query), 60),
+            (profile_identifier.starts_with(query), 50),
+            (display_name.starts_with(query), 40),
+            (display_name.contains(query), 20),
+        ];
+
+        scores
+            .iter()
+            .find(|(is_match, _)| *is_match)
+            .map(|(_, score)| *score)
+    }
 
-            // Create the invite
-            let invite = Invite {
-                invite_type: InviteType::OwnerRequest,
-                updated_at: time(),
-                created_at: time(),
-            };
+    // Method used by the profile canister of a member to push the display name that is used for the member search
+    pub fn set_member_display_name(
+        caller: Principal,
+        principal: Principal,
+        display_name: String,
+    ) -> Result<(), ApiError> {
+        let member = match Self::_get_member_from_caller(principal) {
+            None => {
+                return Err(Self::_member_not_found_error(
+                    "set_member_display_name",
+                    None,
+                ))
+            }
+            Some((_, _member)) => _member,
+        };
 
-            match existing_member {
-                None => {
-                    // If there is no existing member, create a new member
-                    let member = Member {
-                        principal: member_principal,
-                        profile_identifier: Principal::anonymous(),
-                        joined: HashMap::new(),
-                        invites: HashMap::from_iter(vec![(group_identifier, invite)]),
-                    };
+        // Only the canister that stores the profile of the member is allowed to push the display name
+        if Identifier::decode(&member.profile_identifier).1 != caller {
+            return Err(api_error(
+                ApiErrorType::Unauthorized,
+                "UNAUTHORIZED",
+                "Only the profile canister of the member can set the display name",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "set_member_display_name",
+                None,
+            ));
+        }
 
-                    // Add the member to the members array
-                    ENTRIES.with(|entries| {
-                        Data::add_entry(data, entries, member, Some(IDENTIFIER_KIND.to_string()))
-                    })
-                }
-                Some((_identifier, mut _member)) => {
-                    if _member.joined.get(&group_identifier).is_some() {
-                        return Err(api_error(
-                            ApiErrorType::BadRequest,
-                            "ALREADY_JOINED",
-                            "You are already part of this group",
-                            Data::get_name(data.borrow().get()).as_str(),
-                            "invite_to_group",
-                            None,
-                        ));
-                    }
-                    // If there is an existing member, add the invite to the invites array
-                    _member.invites.insert(group_identifier, invite);
+        if display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_DISPLAY_NAME",
+                format!(
+                    "The display name can not be longer than {} characters",
+                    MAX_DISPLAY_NAME_LENGTH
+                )
+                .as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "set_member_display_name",
+                None,
+            ));
+        }
 
-                    // Update the member
-                    ENTRIES.with(|entries| Data::update_entry(data, entries, _identifier, _member))
-                }
+        DISPLAY_NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            match display_name.trim().is_empty() {
+                true => names.remove(&principal.to_string()),
+                false => names.insert(principal.to_string(), display_name),
             }
-        })
+        });
+        Ok(())
     }
 
-    // Method to accept a user request to join a group
-    pub fn accept_user_request_group_invite(
-        member_principal: Principal,
+    // Method to get the member entries of specific user principals, used by the parent canister to resolve members across child canisters
+    pub fn get_members_by_principals(principals: Vec<Principal>) -> Vec<(Principal, Member)> {
+        ENTRIES.with(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .filter(|(_, _member)| principals.contains(&_member.principal))
+                .map(|(_identifier, _member)| {
+                    (
+                        Principal::from_text(_identifier).unwrap_or(Principal::anonymous()),
+                        _member,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    // Method to get the current status of a member within a group, members that are not found are `Active`
+    fn get_member_status(principal: Principal, group_identifier: Principal) -> MemberStatus {
+        Self::_get_member_from_caller(principal)
+            .and_then(|(_, _member)| {
+                _member
+                    .joined
+                    .get(&group_identifier)
+                    .map(|_join| _join.get_status(time()))
+            })
+            .unwrap_or_default()
+    }
+
+    // Method to mute, suspend or reinstate a member within a group, an expiring status is reinstated by a timer
+    pub fn set_member_status(
+        principal: Principal,
+        group_identifier: Principal,
+        status: MemberStatus,
+    ) -> Result<JoinedMemberResponse, ApiError> {
+        let inputs = Some(vec![
+            format!("principal - {}", principal),
+            format!("group_identifier - {}", group_identifier),
+        ]);
+
+        if let Some(_until) = status.get_until() {
+            if _until <= time() {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_UNTIL",
+                    "The status should expire in the future",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "set_member_status",
+                    inputs,
+                ));
+            }
+        }
+
+        let (identifier, mut member) = match Self::_get_member_from_caller(principal) {
+            None => return Err(Self::_member_not_found_error("set_member_status", inputs)),
+            Some(_member) => _member,
+        };
+
+        let join = match member.joined.get_mut(&group_identifier) {
+            None => {
+                return Err(api_error(
+                    ApiErrorType::NotFound,
+                    "NOT_JOINED",
+                    "The member is not part of this group",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "set_member_status",
+                    inputs,
+                ))
+            }
+            Some(_join) => _join,
+        };
+
+        // The owner of the group can not be muted or suspended
+        if join.roles.contains(&"owner".to_string()) && status != MemberStatus::Active {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "CANNOT_MODERATE_OWNER",
+                "The owner of the group can not be muted or suspended",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "set_member_status",
+                inputs,
+            ));
+        }
+
+        join.status = match status {
+            MemberStatus::Active => None,
+            _ => Some(status.clone()),
+        };
+        join.updated_at = time();
+
+        let (_identifier, _member) = STABLE_DATA.with(|data| {
+            ENTRIES.with(|entries| Self::update_entry(data, entries, identifier, member))
+        })?;
+
+        if let Some(_until) = status.get_until() {
+            Self::schedule_reinstatement(_identifier, group_identifier, _until);
+        }
+
+        Ok(Self::map_member_to_joined_member_response(
+            &_identifier,
+            &_member,
+            group_identifier,
+        ))
+    }
+
+    // Method to (re)schedule the reinstatement timers of all muted and suspended members, used on init and post_upgrade
+    // timers do not survive an upgrade, expired statuses are reinstated right away
+    pub fn schedule_reinstatements() {
+        let statuses: Vec<(Principal, Principal, u64)> = ENTRIES.with(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .flat_map(|(_identifier, _member)| {
+                    let identifier =
+                        Principal::from_text(&_identifier).unwrap_or(Principal::anonymous());
+                    _member
+                        .joined
+                        .iter()
+                        .filter_map(|(_group_identifier, _join)| {
+                            _join
+                                .status
+                                .as_ref()
+                                .and_then(|_status| _status.get_until())
+                                .map(|_until| (identifier, *_group_identifier, _until))
+                        })
+                        .collect::<Vec<(Principal, Principal, u64)>>()
+                })
+                .collect()
+        });
+
+        for (identifier, group_identifier, until) in statuses {
+            Self::schedule_reinstatement(identifier, group_identifier, until);
+        }
+    }
+
+    fn schedule_reinstatement(identifier: Principal, group_identifier: Principal, until: u64) {
+        let delay = Duration::from_nanos(until.saturating_sub(time()));
+        set_timer(delay, move || {
+            Self::reinstate_member(identifier, group_identifier, until)
+        });
+    }
+
+    // Method to reinstate a member when the status expired, skipped when the status was changed in the meantime
+    fn reinstate_member(identifier: Principal, group_identifier: Principal, until: u64) {
+        let mut member = match ENTRIES.with(|e| e.borrow().get(&identifier.to_string())) {
+            None => return,
+            Some(_member) => _member,
+        };
+
+        match member.joined.get_mut(&group_identifier) {
+            Some(_join)
+                if _join
+                    .status
+                    .as_ref()
+                    .and_then(|_status| _status.get_until())
+                    == Some(until) =>
+            {
+                _join.status = None;
+                _join.updated_at = time();
+            }
+            _ => return,
+        }
+
+        let _ = STABLE_DATA.with(|data| {
+            ENTRIES.with(|entries| Self::update_entry(data, entries, identifier, member))
+        });
+    }
+
+    // Method to let a member update their own per-group profile fields, admin-only fields are rejected
+    pub async fn update_my_group_profile(
+        caller: Principal,
+        group_identifier: Principal,
+        fields: HashMap<String, String>,
+    ) -> Result<JoinedMemberResponse, ApiError> {
+        // Muted and suspended members can not change their profile within the group
+        if Self::get_member_status(caller, group_identifier) != MemberStatus::Active {
+            return Err(api_error(
+                ApiErrorType::Unauthorized,
+                "MEMBER_NOT_ACTIVE",
+                "Muted or suspended members can not update their group profile",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "update_my_group_profile",
+                None,
+            ));
+        }
+
+        Self::update_group_profile(caller, group_identifier, fields, false).await
+    }
+
+    // Method to let an admin update the per-group profile fields of a member, including the admin-only fields
+    pub async fn update_member_group_profile(
+        principal: Principal,
+        group_identifier: Principal,
+        fields: HashMap<String, String>,
+    ) -> Result<JoinedMemberResponse, ApiError> {
+        Self::update_group_profile(principal, group_identifier, fields, true).await
+    }
+
+    // Method to validate the fields against the schema of the group and merge them into the profile of the member
+    // a field with an empty value is removed from the profile
+    async fn update_group_profile(
+        principal: Principal,
+        group_identifier: Principal,
+        fields: HashMap<String, String>,
+        is_admin: bool,
+    ) -> Result<JoinedMemberResponse, ApiError> {
+        let method_name = match is_admin {
+            true => "update_member_group_profile",
+            false => "update_my_group_profile",
+        };
+        let inputs = Some(vec![
+            format!("principal - {}", principal),
+            format!("group_identifier - {}", group_identifier),
+        ]);
+
+        // Get the profile schema of the group (inter-canister call)
+        let schema = Self::get_member_profile_schema(group_identifier).await?;
+
+        for (key, value) in fields.iter() {
+            let validation_error = match schema.iter().find(|_field| &_field.key == key) {
+                None => Some(format!("The field '{}' is not defined by the group", key)),
+                Some(_field) if _field.admin_only && !is_admin => {
+                    Some(format!("The field '{}' can only be set by an admin", key))
+                }
+                Some(_field)
+                    if value.chars().count() as u64 > _field.max_length
+                        || value.chars().count() > MAX_PROFILE_FIELD_LENGTH =>
+                {
+                    Some(format!("The value of the field '{}' is too long", key))
+                }
+                Some(_) => None,
+            };
+
+            if let Some(_message) = validation_error {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_PROFILE_FIELD",
+                    _message.as_str(),
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    method_name,
+                    inputs,
+                ));
+            }
+        }
+
+        // The member is fetched after the inter-canister call, so changes made during the call are not overwritten
+        let (identifier, mut member) = match Self::_get_member_from_caller(principal) {
+            None => return Err(Self::_member_not_found_error(method_name, inputs)),
+            Some(_member) => _member,
+        };
+
+        let join = match member.joined.get_mut(&group_identifier) {
+            None => {
+                return Err(api_error(
+                    ApiErrorType::NotFound,
+                    "NOT_JOINED",
+                    "The member is not part of this group",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    method_name,
+                    inputs,
+                ))
+            }
+            Some(_join) => _join,
+        };
+
+        let mut profile = join.profile.clone().unwrap_or_default();
+        for (key, value) in fields {
+            match value.trim().is_empty() {
+                true => profile.remove(&key),
+                false => profile.insert(key, value),
+            };
+        }
+
+        if profile.len() > MAX_PROFILE_FIELDS {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "TOO_MANY_PROFILE_FIELDS",
+                format!(
+                    "A group profile can not have more than {} fields",
+                    MAX_PROFILE_FIELDS
+                )
+                .as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                method_name,
+                inputs,
+            ));
+        }
+
+        join.profile = match profile.is_empty() {
+            true => None,
+            false => Some(profile),
+        };
+        join.updated_at = time();
+
+        let (_identifier, _member) = STABLE_DATA.with(|data| {
+            ENTRIES.with(|entries| Self::update_entry(data, entries, identifier, member))
+        })?;
+
+        Ok(Self::map_member_to_joined_member_response(
+            &_identifier,
+            &_member,
+            group_identifier,
+        ))
+    }
+
+    // Method to get the joins and invites of a member by user principal
+    pub fn get_member_groups(
+        principal: Principal,
+    ) -> Option<(Vec<JoinedMemberResponse>, Vec<InviteMemberResponse>)> {
+        Self::_get_member_from_caller(principal).map(|(_identifier, _member)| {
+            let joined = _member
+                .joined
+                .keys()
+                .map(|_group_identifier| {
+                    Self::map_member_to_joined_member_response(
+                        &_identifier,
+                        &_member,
+                        *_group_identifier,
+                    )
+                })
+                .collect();
+
+            let invites = _member
+                .invites
+                .keys()
+                .map(|_group_identifier| {
+                    Self::map_member_to_invite_member_response(
+                        &_identifier,
+                        &_member,
+                        *_group_identifier,
+                    )
+                })
+                .collect();
+
+            (joined, invites)
+        })
+    }
+
+    // Method that is called when a group is created
+    pub async fn add_owner(
+        owner_principal: Principal,
+        group_identifier: Principal,
+    ) -> Result<MemberEntryResponse, ApiError> {
+        // Get the group owner and privacy from an inter-canister call
+        let group_owner_and_privacy =
+            Self::get_group_owner_and_privacy(group_identifier.clone()).await;
+
+        match group_owner_and_privacy {
+            // if the call fails return an error
+            Err(err) => Err(err),
+            Ok((_group_owner, _group_privacy)) => {
+                // Check if the caller is the owner of the group
+                if _group_owner != owner_principal {
+                    return Err(api_error(
+                        ApiErrorType::BadRequest,
+                        "CANT_SET_OWNER",
+                        "You are not the owner of this group",
+                        STABLE_DATA
+                            .with(|data| Data::get_name(data.borrow().get()))
+                            .as_str(),
+                        "add_owner",
+                        None,
+                    ));
+                }
+                // iterate over the members and get the existing member
+                let existing_member = Self::_get_member_from_caller(owner_principal);
+
+                match existing_member {
+                    // If there is no exisiting member, create a new one (or hand it over to a new sibling canister)
+                    None => {
+                        let new_member = Member {
+                            principal: owner_principal,
+                            profile_identifier: Principal::anonymous(),
+                            joined: HashMap::from_iter(vec![(
+                                group_identifier,
+                                Join {
+                                    roles: vec!["owner".to_string()],
+                                    updated_at: time(),
+                                    created_at: time(),
+                                    status: None,
+                                    profile: None,
+                                },
+                            )]),
+                            invites: HashMap::new(),
+                        };
+
+                        Self::add_member_entry(new_member).await
+                    }
+                    Some((_identifier, mut _member)) => {
+                        // if the group identifier is already found in the joined array, throw an error
+                        if _member.joined.contains_key(&group_identifier) {
+                            return Err(api_error(
+                                ApiErrorType::BadRequest,
+                                "ALREADY_JOINED",
+                                "You are already part of this group",
+                                STABLE_DATA
+                                    .with(|data| Data::get_name(data.borrow().get()))
+                                    .as_str(),
+                                "add_owner",
+                                None,
+                            ));
+                        }
+
+                        // Add the group identifier to the joined array
+                        _member.joined.insert(
+                            group_identifier,
+                            Join {
+                                roles: vec!["owner".to_string()],
+                                updated_at: time(),
+                                created_at: time(),
+                                status: None,
+                                profile: None,
+                            },
+                        );
+
+                        let response = STABLE_DATA.with(|data| {
+                            ENTRIES.with(|entries| {
+                                Self::update_entry(data, entries, _identifier, _member)
+                            })
+                        });
+                        response.map(|(_identifier, _member)| MemberEntryResponse {
+                            canister: id(),
+                            identifier: Some(_identifier),
+                            member: _member,
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    // Method to store a new member entry
+    // If this canister is at capacity, a sibling canister is spawned and the member is stored on the sibling instead
+    async fn add_member_entry(member: Member) -> Result<MemberEntryResponse, ApiError> {
+        let result = match Self::is_at_configured_capacity() {
+            // The configured max entries is reached, handle it the same as a full canister
+            true => Err(api_error(
+                ApiErrorType::CanisterAtCapacity,
+                "CANISTER_AT_CAPACITY",
+                "The configured max entries of this canister is reached",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "add_member_entry",
+                None,
+            )),
+            false => STABLE_DATA.with(|data| {
+                ENTRIES.with(|entries| {
+                    Self::add_entry(
+                        data,
+                        entries,
+                        member.clone(),
+                        Some(IDENTIFIER_KIND.to_string()),
+                    )
+                })
+            }),
+        };
+
+        match result {
+            Ok((_identifier, _member)) => Ok(MemberEntryResponse {
+                canister: id(),
+                identifier: Some(_identifier),
+                member: _member,
+            }),
+            // The member was not added to the data store because the canister is at capacity
+            Err(ApiError::CanisterAtCapacity(_)) => {
+                let _data = STABLE_DATA.with(|v| v.borrow().get().clone());
+                // Spawn a sibling canister and pass the member data to it
+                match Data::spawn_sibling(&_data, member.clone()).await {
+                    Ok(_sibling_principal) => {
+                        // Mark this canister as unavailable so new entries are no longer accepted
+                        STABLE_DATA.with(|data| {
+                            let mut _data = data.borrow().get().clone();
+                            _data.is_available = false;
+                            let _ = data.borrow_mut().set(_data);
+                        });
+                        Ok(MemberEntryResponse {
+                            canister: _sibling_principal,
+                            identifier: None,
+                            member,
+                        })
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Method to invite a member to a group
+    pub fn invite_to_group(
+        group_identifier: Principal,
+        member_principal: Principal,
+    ) -> Result<(Principal, Member), ApiError> {
+        STABLE_DATA.with(|data| {
+            // Get the existing member
+            let existing_member = Self::_get_member_from_caller(member_principal);
+
+            // Create the invite
+            let invite = Invite {
+                invite_type: InviteType::OwnerRequest,
+                updated_at: time(),
+                created_at: time(),
+                answers: None,
+            };
+
+            match existing_member {
+                None => {
+                    // If there is no existing member, create a new member
+                    let member = Member {
+                        principal: member_principal,
+                        profile_identifier: Principal::anonymous(),
+                        joined: HashMap::new(),
+                        invites: HashMap::from_iter(vec![(group_identifier, invite)]),
+                    };
+
+                    // Add the member to the members array
+                    ENTRIES.with(|entries| {
+                        Self::add_entry(data, entries, member, Some(IDENTIFIER_KIND.to_string()))
+                    })
+                }
+                Some((_identifier, mut _member)) => {
+                    if _member.joined.get(&group_identifier).is_some() {
+                        return Err(api_error(
+                            ApiErrorType::BadRequest,
+                            "ALREADY_JOINED",
+                            "You are already part of this group",
+                            Data::get_name(data.borrow().get()).as_str(),
+                            "invite_to_group",
+                            None,
+                        ));
+                    }
+                    // If there is an existing member, add the invite to the invites array
+                    _member.invites.insert(group_identifier, invite);
+
+                    // Update the member
+                    ENTRIES.with(|entries| Self::update_entry(data, entries, _identifier, _member))
+                }
+            }
+        })
+    }
+
+    // Method to accept a user request to join a group
+    pub async fn accept_user_request_group_invite(
+        member_principal: Principal,
+        group_identifier: Principal,
+    ) -> Result<(Principal, Member), ApiError> {
+        // Past the capacity of the group the invite is kept and the member is added to the waitlist
+        let seat_claimed =
+            Self::claim_invite_seat(member_principal, group_identifier, InviteType::UserRequest)
+                .await?;
+
+        let result = Self::_accept_user_request_group_invite(member_principal, group_identifier);
+        if seat_claimed && result.is_err() {
+            Self::release_group_seat(group_identifier).await;
+        }
+        result
+    }
+
+    fn _accept_user_request_group_invite(
+        member_principal: Principal,
         group_identifier: Principal,
     ) -> Result<(Principal, Member), ApiError> {
         // Get the existing member
@@ -1212,90 +1973,473 @@ impl Store {
                                 roles: vec!["member".to_string()],
                                 updated_at: time(),
                                 created_at: time(),
+                                status: None,
+                                profile: None,
                             },
                         );
 
                         // Update the member
                         let result = STABLE_DATA.with(|data| {
                             ENTRIES.with(|entries| {
-                                Data::update_entry(data, entries, _identifier, _member)
+                                Self::update_entry(data, entries, _identifier, _member)
                             })
                         });
 
-                        // Update the member count on the group canister (inter-canister call)
-                        ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
-                        result
-                    }
-                }
+                        // Update the member count on the group canister (inter-canister call)
+                        ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+                        result
+                    }
+                }
+            }
+        }
+    }
+
+    // Method to accept an owner request to join a group
+    pub async fn accept_owner_request_group_invite(
+        caller: Principal,
+        group_identifier: Principal,
+    ) -> Result<(Principal, Member), ApiError> {
+        // Past the capacity of the group the invite is kept and the member is added to the waitlist
+        let seat_claimed =
+            Self::claim_invite_seat(caller, group_identifier, InviteType::OwnerRequest).await?;
+
+        let result = Self::_accept_owner_request_group_invite(caller, group_identifier);
+        if seat_claimed && result.is_err() {
+            Self::release_group_seat(group_identifier).await;
+        }
+        result
+    }
+
+    fn _accept_owner_request_group_invite(
+        caller: Principal,
+        group_identifier: Principal,
+    ) -> Result<(Principal, Member), ApiError> {
+        STABLE_DATA.with(|data| {
+            // Get the existing member
+            match Self::_get_member_from_caller(caller) {
+                // If there is no member, throw an error
+                None => Err(Self::_member_not_found_error(
+                    "accept_owner_request_group_invite",
+                    None,
+                )),
+                // If there is a member, continue
+                Some((_identifier, mut _member)) => {
+                    // Find the invite in the invites array
+                    match _member.invites.get(&group_identifier) {
+                        // If there is no invite, throw an error
+                        None => Err(api_error(
+                            ApiErrorType::NotFound,
+                            "NO_INVITE_FOUND",
+                            "There is no invite found for this group",
+                            Data::get_name(data.borrow().get()).as_str(),
+                            "accept_owner_request_group_invite",
+                            None,
+                        )),
+                        // If there is an invite, continue
+                        Some(_invite) => {
+                            // Check if the invite type is owner request
+                            if _invite.invite_type != InviteType::OwnerRequest {
+                                return Err(api_error(
+                                    ApiErrorType::BadRequest,
+                                    "INVALID_TYPE",
+                                    "Invalid invite type",
+                                    Data::get_name(data.borrow().get()).as_str(),
+                                    "accept_owner_request_group_invite",
+                                    None,
+                                ));
+                            }
+
+                            // Remove the invite from the invites array
+                            _member.invites.remove(&group_identifier);
+
+                            // Add a new Join to the joined array
+                            _member.joined.insert(
+                                group_identifier.clone(),
+                                Join {
+                                    roles: vec!["member".to_string()],
+                                    updated_at: time(),
+                                    created_at: time(),
+                                    status: None,
+                                    profile: None,
+                                },
+                            );
+                            // Update the member
+                            let result = ENTRIES.with(|entries| {
+                                Self::update_entry(data, entries, _identifier, _member)
+                            });
+
+                            // Update the member count on the group canister (inter-canister call)
+                            ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+                            result
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    // Method to create an invite code for a group, the codes are stored on the parent canister (inter-canister call)
+    // the default role has to be one of the roles of the group, the owner role is rejected by the parent canister
+    pub async fn create_invite_code(
+        caller: Principal,
+        request: InviteCodeRequest,
+    ) -> Result<InviteCode, ApiError> {
+        if let Some(_default_role) = &request.default_role {
+            // Get the roles of the group (inter-canister call)
+            let group_roles = get_group_roles(request.group_identifier).await;
+            if group_roles.is_err() {
+                Metrics::increment(
+                    "inter_canister_call_failures",
+                    &[("method", "get_group_roles")],
+                );
+            }
+
+            let role_exists = group_roles
+                .unwrap_or_default()
+                .iter()
+                .chain(default_roles().iter())
+                .any(|_role| &_role.name == _default_role);
+
+            if !role_exists {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_ROLE",
+                    "The default role is not a role of the group",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "create_invite_code",
+                    Some(vec![format!("default_role - {}", _default_role)]),
+                ));
+            }
+        }
+
+        Self::call_parent("create_invite_code", (caller, request)).await
+    }
+
+    // Method to get the invite codes of a group from the parent canister (inter-canister call)
+    pub async fn get_invite_codes(
+        group_identifier: Principal,
+    ) -> Result<Vec<InviteCode>, ApiError> {
+        Self::call_parent("get_invite_codes", (group_identifier,)).await
+    }
+
+    // Method to revoke an invite code of a group on the parent canister (inter-canister call)
+    pub async fn revoke_invite_code(
+        group_identifier: Principal,
+        code: String,
+    ) -> Result<InviteCode, ApiError> {
+        Self::call_parent("revoke_invite_code", (group_identifier, code)).await
+    }
+
+    // Method to get the redemptions of an invite code of a group from the parent canister (inter-canister call)
+    pub async fn get_invite_code_redemptions(
+        group_identifier: Principal,
+        code: String,
+    ) -> Result<Vec<InviteCodeRedemption>, ApiError> {
+        Self::call_parent("get_invite_code_redemptions", (group_identifier, code)).await
+    }
+
+    // Method to join a group with an invite code, the member gets the default role of the code
+    // the use is counted by the parent canister before the join is added, which also makes sure a code is redeemed once per principal
+    // when the join can not be added afterwards the redemption is cancelled so the use is not lost
+    pub async fn redeem_invite_code(
+        caller: Principal,
+        code: String,
+    ) -> Result<MemberEntryResponse, ApiError> {
+        let invite_code: InviteCode = Self::call_parent("get_invite_code", (code.clone(),)).await?;
+        let group_identifier = invite_code.group_identifier;
+
+        // Members of the group can not redeem a code of the group, checked before the use is counted
+        let is_joined = Self::_get_member_from_caller(caller)
+            .is_some_and(|(_, _member)| _member.joined.contains_key(&group_identifier));
+        if is_joined {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "ALREADY_JOINED",
+                "You are already part of this group",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "redeem_invite_code",
+                None,
+            ));
+        }
+
+        let invite_code: InviteCode =
+            Self::call_parent("redeem_invite_code", (code.clone(), caller)).await?;
+        let role = invite_code
+            .default_role
+            .unwrap_or_else(|| "member".to_string());
+
+        // Past the capacity of the group the member is added to the waitlist with the default role of the code,
+        // a waitlisted member keeps the redemption as the join is added once the member is promoted
+        let claim: SeatClaim =
+            match Self::call_parent("claim_group_seat", (group_identifier, caller, role.clone()))
+                .await
+            {
+                Err(err) => {
+                    Self::cancel_invite_code_redemption(code, caller).await;
+                    return Err(err);
+                }
+                Ok(_claim) => _claim,
+            };
+        Self::check_seat_claim(claim)?;
+
+        match Self::add_join(caller, group_identifier, role, "redeem_invite_code").await {
+            Err(err) => {
+                Self::release_group_seat(group_identifier).await;
+                Self::cancel_invite_code_redemption(code, caller).await;
+                Err(err)
+            }
+            Ok(_response) => Ok(_response),
+        }
+    }
+
+    // Method to cancel a redemption of an invite code on the parent canister (inter-canister call)
+    async fn cancel_invite_code_redemption(code: String, principal: Principal) {
+        let result: Result<InviteCode, ApiError> =
+            Self::call_parent("cancel_invite_code_redemption", (code.clone(), principal)).await;
+
+        if let Err(err) = result {
+            add_log(PostLog {
+                log_type: LogType::Error,
+                description: "Invite code redemption not cancelled".to_string(),
+                source: "redeem_invite_code".to_string(),
+                data: format!("code: {}, principal: {}, error: {:?}", code, principal, err),
+            });
+        }
+    }
+
+    // Method used by the parent canister to add a member that is promoted from the waitlist of a group
+    pub async fn add_waitlisted_member(
+        group_identifier: Principal,
+        principal: Principal,
+        role: String,
+    ) -> Result<MemberEntryResponse, ApiError> {
+        Self::add_join(principal, group_identifier, role, "add_waitlisted_member").await
+    }
+
+    // Method to add a join with a role to the member of a principal, a new member is created when there is none
+    async fn add_join(
+        principal: Principal,
+        group_identifier: Principal,
+        role: String,
+        method_name: &str,
+    ) -> Result<MemberEntryResponse, ApiError> {
+        let join = Join {
+            roles: vec![role],
+            updated_at: time(),
+            created_at: time(),
+            status: None,
+            profile: None,
+        };
+
+        let result = match Self::_get_member_from_caller(principal) {
+            // If there is no existing member, add a new one (or hand it over to a new sibling canister)
+            None => {
+                Self::add_member_entry(Member {
+                    principal,
+                    profile_identifier: Principal::anonymous(),
+                    joined: HashMap::from_iter(vec![(group_identifier, join)]),
+                    invites: HashMap::new(),
+                })
+                .await
+            }
+            Some((_identifier, mut _member)) => {
+                if _member.joined.contains_key(&group_identifier) {
+                    return Err(api_error(
+                        ApiErrorType::BadRequest,
+                        "ALREADY_JOINED",
+                        "You are already part of this group",
+                        STABLE_DATA
+                            .with(|data| Data::get_name(data.borrow().get()))
+                            .as_str(),
+                        method_name,
+                        None,
+                    ));
+                }
+
+                // A pending invite for the group is replaced by the join
+                _member.invites.remove(&group_identifier);
+                _member.joined.insert(group_identifier, join);
+                STABLE_DATA
+                    .with(|data| {
+                        ENTRIES
+                            .with(|entries| Self::update_entry(data, entries, _identifier, _member))
+                    })
+                    .map(|(_identifier, _member)| MemberEntryResponse {
+                        canister: id(),
+                        identifier: Some(_identifier),
+                        member: _member,
+                    })
+            }
+        };
+
+        // fire and forget inter canister call to update the group member count on the group canister
+        ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
+        result
+    }
+
+    // Method to set or remove the capacity of a group, the seats are counted on the parent canister (inter-canister call)
+    pub async fn set_group_capacity(
+        group_identifier: Principal,
+        capacity: Option<u64>,
+    ) -> Result<Option<GroupCapacity>, ApiError> {
+        Self::call_parent("set_group_capacity", (group_identifier, capacity)).await
+    }
+
+    // Method to get the capacity, taken seats and waitlist of a group from the parent canister (inter-canister call)
+    pub async fn get_group_capacity(
+        group_identifier: Principal,
+    ) -> Result<Option<GroupCapacity>, ApiError> {
+        Self::call_parent("get_group_capacity", (group_identifier,)).await
+    }
+
+    // Method to claim a seat of a group on the parent canister before a join is added (inter-canister call)
+    // returns a `WAITLISTED` error when the group is at capacity, the join is added when the member is promoted
+    async fn claim_group_seat(
+        group_identifier: Principal,
+        principal: Principal,
+        role: String,
+    ) -> Result<(), ApiError> {
+        let claim: SeatClaim =
+            Self::call_parent("claim_group_seat", (group_identifier, principal, role)).await?;
+        Self::check_seat_claim(claim)
+    }
+
+    // Method to turn a waitlisted seat claim into an error, the member is added once promoted from the waitlist
+    fn check_seat_claim(claim: SeatClaim) -> Result<(), ApiError> {
+        match claim {
+            SeatClaim::Granted => Ok(()),
+            SeatClaim::Waitlisted { position } => Err(api_error(
+                ApiErrorType::BadRequest,
+                "WAITLISTED",
+                format!(
+                    "The group is at capacity, you are on the waitlist at position {}",
+                    position
+                )
+                .as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "claim_group_seat",
+                None,
+            )),
+        }
+    }
+
+    // Method to claim a seat for accepting an invite, skipped when there is no invite of the type so the accept call reports it
+    // returns if a seat is claimed, so it can be released when the invite can not be accepted
+    async fn claim_invite_seat(
+        principal: Principal,
+        group_identifier: Principal,
+        invite_type: InviteType,
+    ) -> Result<bool, ApiError> {
+        let has_invite = Self::_get_member_from_caller(principal).is_some_and(|(_, _member)| {
+            _member
+                .invites
+                .get(&group_identifier)
+                .is_some_and(|_invite| _invite.invite_type == invite_type)
+        });
+
+        match has_invite {
+            true => Self::claim_group_seat(group_identifier, principal, "member".to_string())
+                .await
+                .map(|_| true),
+            false => Ok(false),
+        }
+    }
+
+    // Method to release a seat of a group on the parent canister when a member leaves or is removed (inter-canister call)
+    // a release that keeps failing is logged, the seats are counted again when the capacity of the group is set
+    async fn release_group_seat(group_identifier: Principal) {
+        let mut last_error: Option<ApiError> = None;
+        for _ in 0..RELEASE_SEAT_ATTEMPTS {
+            let result: Result<(), ApiError> =
+                Self::call_parent("release_group_seat", (group_identifier,)).await;
+            match result {
+                Ok(_) => return,
+                Err(err) => last_error = Some(err),
             }
         }
+
+        add_log(PostLog {
+            log_type: LogType::Error,
+            description: "Group seat not released".to_string(),
+            source: "release_group_seat".to_string(),
+            data: format!("group: {}, error: {:?}", group_identifier, last_error),
+        });
     }
 
-    // Method to accept an owner request to join a group
-    pub fn accept_owner_request_group_invite(
+    // Method to let the caller leave the waitlist of a group (inter-canister call)
+    pub async fn leave_waitlist(
         caller: Principal,
         group_identifier: Principal,
-    ) -> Result<(Principal, Member), ApiError> {
-        STABLE_DATA.with(|data| {
-            // Get the existing member
-            match Self::_get_member_from_caller(caller) {
-                // If there is no member, throw an error
-                None => Err(Self::_member_not_found_error(
-                    "accept_owner_request_group_invite",
-                    None,
-                )),
-                // If there is a member, continue
-                Some((_identifier, mut _member)) => {
-                    // Find the invite in the invites array
-                    match _member.invites.get(&group_identifier) {
-                        // If there is no invite, throw an error
-                        None => Err(api_error(
-                            ApiErrorType::NotFound,
-                            "NO_INVITE_FOUND",
-                            "There is no invite found for this group",
-                            Data::get_name(data.borrow().get()).as_str(),
-                            "accept_owner_request_group_invite",
-                            None,
-                        )),
-                        // If there is an invite, continue
-                        Some(_invite) => {
-                            // Check if the invite type is owner request
-                            if _invite.invite_type != InviteType::OwnerRequest {
-                                return Err(api_error(
-                                    ApiErrorType::BadRequest,
-                                    "INVALID_TYPE",
-                                    "Invalid invite type",
-                                    Data::get_name(data.borrow().get()).as_str(),
-                                    "accept_owner_request_group_invite",
-                                    None,
-                                ));
-                            }
+    ) -> Result<(), ApiError> {
+        let removed: bool =
+            Self::call_parent("remove_from_waitlist", (group_identifier, caller)).await?;
+        match removed {
+            true => Ok(()),
+            false => Err(api_error(
+                ApiErrorType::NotFound,
+                "NOT_WAITLISTED",
+                "You are not on the waitlist of this group",
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                "leave_waitlist",
+                Some(vec![format!("group_identifier - {}", group_identifier)]),
+            )),
+        }
+    }
 
-                            // Remove the invite from the invites array
-                            _member.invites.remove(&group_identifier);
+    // Method to remove a member from the waitlist of a group on the parent canister when its invite or join is removed
+    // a removal that fails is logged, the member is then still promoted when a seat becomes available
+    async fn drop_waitlist_entry(group_identifier: Principal, principal: Principal) {
+        let result: Result<bool, ApiError> =
+            Self::call_parent("remove_from_waitlist", (group_identifier, principal)).await;
+
+        if let Err(err) = result {
+            add_log(PostLog {
+                log_type: LogType::Error,
+                description: "Waitlist entry not removed".to_string(),
+                source: "drop_waitlist_entry".to_string(),
+                data: format!(
+                    "group: {}, principal: {}, error: {:?}",
+                    group_identifier, principal, err
+                ),
+            });
+        }
+    }
 
-                            // Add a new Join to the joined array
-                            _member.joined.insert(
-                                group_identifier.clone(),
-                                Join {
-                                    roles: vec!["member".to_string()],
-                                    updated_at: time(),
-                                    created_at: time(),
-                                },
-                            );
-                            // Update the member
-                            let result = ENTRIES.with(|entries| {
-                                Data::update_entry(data, entries, _identifier, _member)
-                            });
+    // Method to call the parent canister, used for the data that is shared by all child canisters (inter-canister call)
+    async fn call_parent<A, R>(method_name: &str, args: A) -> Result<R, ApiError>
+    where
+        A: ArgumentEncoder,
+        R: CandidType + DeserializeOwned,
+    {
+        let parent = STABLE_DATA.with(|data| data.borrow().get().parent);
+        let response: Result<(Result<R, ApiError>,), _> =
+            call::call(parent, method_name, args).await;
+
+        if response.is_err() {
+            Metrics::increment("inter_canister_call_failures", &[("method", method_name)]);
+        }
 
-                            // Update the member count on the group canister (inter-canister call)
-                            ic_cdk::spawn(Self::update_member_count_on_group(group_identifier));
-                            result
-                        }
-                    }
-                }
-            }
-        })
+        match response {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "INTER_CANISTER_CALL_FAILED",
+                err.1.as_str(),
+                STABLE_DATA
+                    .with(|data| Data::get_name(data.borrow().get()))
+                    .as_str(),
+                method_name,
+                None,
+            )),
+            Ok((_result,)) => _result,
+        }
     }
 
     // Method to get the group owner and privacy from the group canister (inter-canister call)
@@ -1310,6 +2454,13 @@ impl Store {
             )
             .await;
 
+        if group_privacy_response.is_err() {
+            Metrics::increment(
+                "inter_canister_call_failures",
+                &[("method", "get_group_owner_and_privacy")],
+            );
+        }
+
         STABLE_DATA.with(|data| match group_privacy_response {
             Err(err) => Err(api_error(
                 ApiErrorType::BadRequest,
@@ -1326,6 +2477,76 @@ impl Store {
         })
     }
 
+    // Method to get the join questionnaire from the group canister (inter-canister call)
+    // group canisters that do not implement `get_join_questionnaire` yet have no questionnaire
+    async fn get_join_questionnaire(
+        group_identifier: Principal,
+    ) -> Result<Vec<JoinQuestion>, ApiError> {
+        let questionnaire_response: Result<(Result<Vec<JoinQuestion>, ApiError>,), _> = call::call(
+            Identifier::decode(&group_identifier).1,
+            "get_join_questionnaire",
+            (group_identifier,),
+        )
+        .await;
+
+        if let Err((_, _message)) = &questionnaire_response {
+            if _message.contains("has no update method") {
+                return Ok(vec![]);
+            }
+        }
+
+        if questionnaire_response.is_err() {
+            Metrics::increment(
+                "inter_canister_call_failures",
+                &[("method", "get_join_questionnaire")],
+            );
+        }
+
+        STABLE_DATA.with(|data| match questionnaire_response {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "INTER_CANISTER_CALL_FAILED",
+                err.1.as_str(),
+                Data::get_name(data.borrow().get()).as_str(),
+                "get_join_questionnaire",
+                None,
+            )),
+            Ok((_questions,)) => _questions,
+        })
+    }
+
+    // Method to get the member profile schema from the group canister (inter-canister call)
+    async fn get_member_profile_schema(
+        group_identifier: Principal,
+    ) -> Result<Vec<ProfileFieldDefinition>, ApiError> {
+        let schema_response: Result<(Result<Vec<ProfileFieldDefinition>, ApiError>,), _> =
+            call::call(
+                Identifier::decode(&group_identifier).1,
+                "get_member_profile_schema",
+                (group_identifier,),
+            )
+            .await;
+
+        if schema_response.is_err() {
+            Metrics::increment(
+                "inter_canister_call_failures",
+                &[("method", "get_member_profile_schema")],
+            );
+        }
+
+        STABLE_DATA.with(|data| match schema_response {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "INTER_CANISTER_CALL_FAILED",
+                err.1.as_str(),
+                Data::get_name(data.borrow().get()).as_str(),
+                "get_member_profile_schema",
+                None,
+            )),
+            Ok((_schema,)) => _schema,
+        })
+    }
+
     // Method to map a member to a joined member response
     fn map_member_to_joined_member_response(
         identifier: &Principal,
@@ -1333,10 +2554,16 @@ impl Store {
         group_identifier: Principal,
     ) -> JoinedMemberResponse {
         let mut roles: Vec<String> = vec![];
+        let mut status = MemberStatus::Active;
+        let mut profile: HashMap<String, String> = HashMap::new();
 
         match member.joined.get(&group_identifier) {
             None => {}
-            Some(_join) => roles = _join.roles.clone(),
+            Some(_join) => {
+                roles = _join.roles.clone();
+                status = _join.get_status(time());
+                profile = _join.profile.clone().unwrap_or_default();
+            }
         }
 
         JoinedMemberResponse {
@@ -1344,6 +2571,8 @@ impl Store {
             member_identifier: identifier.clone(),
             principal: member.principal,
             roles,
+            status: Some(status),
+            profile: Some(profile),
         }
     }
 
@@ -1469,6 +2698,20 @@ impl Store {
         .await
     }
 
+    // Method to check if a member has the moderation permission
+    pub async fn can_moderate_member(
+        caller: Principal,
+        group_identifier: Principal,
+    ) -> Result<Principal, ApiError> {
+        Self::check_named_permission(
+            caller,
+            group_identifier,
+            PermissionActionType::Edit,
+            MODERATION_PERMISSION.to_string(),
+        )
+        .await
+    }
+
     // Method to check if a member has a specific permission
     pub async fn can_delete_member(
         caller: Principal,
@@ -1545,9 +2788,31 @@ impl Store {
         group_identifier: Principal,
         permission: PermissionActionType,
         permission_type: PermissionType,
+    ) -> Result<Principal, ApiError> {
+        Self::check_named_permission(
+            caller,
+            group_identifier,
+            permission,
+            permission_type.to_string(),
+        )
+        .await
+    }
+
+    // Method to check if a member has a permission by its name within the group roles
+    async fn check_named_permission(
+        caller: Principal,
+        group_identifier: Principal,
+        permission: PermissionActionType,
+        permission_name: String,
     ) -> Result<Principal, ApiError> {
         // Get the roles of the group (inter-canister call)
         let group_roles = get_group_roles(group_identifier).await;
+        if group_roles.is_err() {
+            Metrics::increment(
+                "inter_canister_call_failures",
+                &[("method", "get_group_roles")],
+            );
+        }
         // Get the roles of the member
         let member_roles = Self::get_member_roles_by_principal(caller, group_identifier);
 
@@ -1568,12 +2833,46 @@ impl Store {
                     ));
                 }
 
+                // Suspended members lose all permissions, muted members only keep the read permission
+                match Self::get_member_status(caller, group_identifier) {
+                    MemberStatus::Suspended { .. } => {
+                        return Err(api_error(
+                            ApiErrorType::Unauthorized,
+                            "MEMBER_SUSPENDED",
+                            "You are suspended in this group",
+                            STABLE_DATA
+                                .with(|data| Data::get_name(data.borrow().get()))
+                                .as_str(),
+                            "check_permission",
+                            None,
+                        ));
+                    }
+                    MemberStatus::Muted { .. }
+                        if !matches!(permission, PermissionActionType::Read) =>
+                    {
+                        return Err(api_error(
+                            ApiErrorType::Unauthorized,
+                            "MEMBER_MUTED",
+                            "You are muted in this group",
+                            STABLE_DATA
+                                .with(|data| Data::get_name(data.borrow().get()))
+                                .as_str(),
+                            "check_permission",
+                            None,
+                        ));
+                    }
+                    _ => {}
+                }
+
                 match group_roles {
                     // If the group has roles, check if the member has the permission
-                    Ok(mut _group_roles) => {
-                        _group_roles.append(&mut default_roles());
-                        let has_permission =
-                            has_permission(&_roles, &permission_type, &_group_roles, &permission);
+                    Ok(_group_roles) => {
+                        let has_permission = Self::has_named_permission(
+                            &_roles,
+                            &permission_name,
+                            &Self::with_default_roles(_group_roles),
+                            &permission,
+                        );
 
                         // If the member doesn't have the permission, return an unauthorized error
                         if !has_permission {
@@ -1618,6 +2917,245 @@ impl Store {
         }
     }
 
+    // Method to check if one of the roles of a member has the named permission for the action
+    fn has_named_permission(
+        member_roles: &[String],
+        permission_name: &str,
+        group_roles: &[GroupRole],
+        permission: &PermissionActionType,
+    ) -> bool {
+        use PermissionActionType::*;
+        group_roles
+            .iter()
+            .filter(|_role| member_roles.contains(&_role.name))
+            .flat_map(|_role| _role.permissions.iter())
+            .any(|_permission| {
+                _permission.name == permission_name
+                    && match permission {
+                        Write => _permission.actions.write,
+                        Read => _permission.actions.read,
+                        Edit => _permission.actions.edit,
+                        Delete => _permission.actions.delete,
+                    }
+            })
+    }
+
+    // Method to add the default roles to the roles of a group
+    // the protected `owner`, `admin` and `moderator` roles get the moderation permission
+    fn with_default_roles(mut group_roles: Vec<GroupRole>) -> Vec<GroupRole> {
+        for mut role in default_roles() {
+            if ["owner", "admin", "moderator"].contains(&role.name.as_str()) {
+                role.permissions.push(Permission {
+                    name: MODERATION_PERMISSION.to_string(),
+                    protected: true,
+                    actions: PermissionActions {
+                        write: false,
+                        read: true,
+                        edit: true,
+                        delete: false,
+                    },
+                });
+            }
+            group_roles.push(role);
+        }
+        group_roles
+    }
+
+    // Method to update a member entry, its certified membership keys and the snapshots of the affected groups
+    fn update_entry(
+        data: &RefCell<StableCell<Data, Memory>>,
+        entries: &RefCell<StableBTreeMap<String, Member, Memory>>,
+        identifier: Principal,
+        member: Member,
+    ) -> Result<(Principal, Member), ApiError> {
+        let previous_member = entries.borrow().get(&identifier.to_string());
+        let result = Data::update_entry(data, entries, identifier, member);
+        if let Ok((_, _member)) = &result {
+            Certification::certify_member(_member);
+            Self::invalidate_group_snapshots(previous_member.as_ref(), _member);
+        }
+        result
+    }
+
+    // Method to add a member entry, its certified membership keys and the snapshots of the affected groups
+    fn add_entry(
+        data: &RefCell<StableCell<Data, Memory>>,
+        entries: &RefCell<StableBTreeMap<String, Member, Memory>>,
+        member: Member,
+        kind: Option<String>,
+    ) -> Result<(Principal, Member), ApiError> {
+        let result = Data::add_entry(data, entries, member, kind);
+        if let Ok((_, _member)) = &result {
+            Certification::certify_member(_member);
+            Self::invalidate_group_snapshots(None, _member);
+        }
+        result
+    }
+
+    // Method to add a member entry that is handed over by the parent canister, its certified membership keys and the snapshots of the affected groups
+    pub fn add_entry_by_parent(
+        data: &RefCell<StableCell<Data, Memory>>,
+        entries: &RefCell<StableBTreeMap<String, Member, Memory>>,
+        caller: Principal,
+        entry: Vec<u8>,
+    ) -> Result<(), ApiError> {
+        let member = deserialize::<Member>(entry.clone()).ok();
+        let result = Data::add_entry_by_parent(data, entries, caller, entry, None);
+        if let (Ok(_), Some(_member)) = (&result, &member) {
+            Certification::certify_member(_member);
+            Self::invalidate_group_snapshots(None, _member);
+        }
+        result
+    }
+
+    // Method to invalidate the snapshots of every group the member joined or was invited to, before or after a write
+    fn invalidate_group_snapshots(previous_member: Option<&Member>, member: &Member) {
+        let mut group_identifiers: Vec<Principal> = member
+            .joined
+            .keys()
+            .chain(member.invites.keys())
+            .cloned()
+            .collect();
+
+        if let Some(_previous_member) = previous_member {
+            group_identifiers.extend(
+                _previous_member
+                    .joined
+                    .keys()
+                    .chain(_previous_member.invites.keys())
+                    .cloned(),
+            );
+        }
+
+        group_identifiers.sort();
+        group_identifiers.dedup();
+        SnapshotCache::invalidate(group_identifiers);
+    }
+
+    // Method to check if the max entries pushed by the parent canister is reached
+    fn is_at_configured_capacity() -> bool {
+        match CONFIG.with(|config| config.borrow().get().max_entries) {
+            None => false,
+            Some(_max_entries) => ENTRIES.with(|entries| entries.borrow().len()) >= _max_entries,
+        }
+    }
+
+    // Method used by the parent canister to check if this canister is healthy after an upgrade
+    // reads the stored data, config and the first and last member entry
+    pub fn self_test() -> Result<(), String> {
+        let parent = STABLE_DATA.with(|data| data.borrow().get().parent);
+        if parent == Principal::anonymous() {
+            return Err("No parent set".to_string());
+        }
+
+        let _ = Self::get_config();
+
+        ENTRIES.with(|entries| {
+            let entries = entries.borrow();
+            if !entries.is_empty() {
+                let _ = entries.first_key_value();
+                let _ = entries.last_key_value();
+            }
+        });
+
+        Ok(())
+    }
+
+    // Method to get the counts of the stored member entries, joins and invites
+    pub fn get_member_stats() -> MemberStats {
+        ENTRIES.with(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .fold(MemberStats::default(), |mut stats, (_, member)| {
+                    stats.entries += 1;
+                    stats.joins += member.joined.len() as u64;
+                    stats.invites += member.invites.len() as u64;
+                    stats
+                })
+        })
+    }
+
+    // Method to get the config that is pushed by the parent canister
+    pub fn get_config() -> ChildConfig {
+        CONFIG.with(|config| config.borrow().get().clone())
+    }
+
+    // Method to store the config that is pushed by the parent canister
+    pub fn set_config(config: ChildConfig) -> Result<(), ApiError> {
+        CONFIG
+            .with(|c| c.borrow_mut().set(config))
+            .map(|_| ())
+            .map_err(|_| {
+                api_error(
+                    ApiErrorType::BadRequest,
+                    "CONFIG_NOT_STORED",
+                    "The config could not be stored",
+                    STABLE_DATA
+                        .with(|data| Data::get_name(data.borrow().get()))
+                        .as_str(),
+                    "set_config",
+                    None,
+                )
+            })
+    }
+
+    // Used for composite_query calls from the parent canister
+    //
+    // Method to get a chunk of the serialized members of a group (versioned chunk protocol)
+    pub fn get_join_data_chunk(request: ChunkRequest) -> Result<DataChunk, ChunkError> {
+        let codec = ChunkCodec::negotiate(&request.accepted_codecs);
+        let request = Self::cap_chunk_request(request);
+
+        // Serve the chunk from the cached snapshot of the group when it is up-to-date
+        if let Some(_chunk) = SnapshotCache::with_snapshot(
+            request.group_identifier,
+            SnapshotKind::Members,
+            codec,
+            |bytes, hash| DataChunk::from_bytes(bytes, hash, &request, codec),
+        ) {
+            return _chunk;
+        }
+
+        let bytes = serialize(&Self::get_group_members(request.group_identifier))
+            .map_err(|err| ChunkError::SerializationFailed(err.to_string()))?;
+        let encoded = codec.encode(bytes)?;
+        DataChunk::from_bytes(&encoded, &checksum(&encoded), &request, codec)
+    }
+
+    // Used for composite_query calls from the parent canister
+    //
+    // Method to get a chunk of the serialized invites of a group (versioned chunk protocol)
+    pub fn get_invite_data_chunk(request: ChunkRequest) -> Result<DataChunk, ChunkError> {
+        let codec = ChunkCodec::negotiate(&request.accepted_codecs);
+        let request = Self::cap_chunk_request(request);
+
+        // Serve the chunk from the cached snapshot of the group when it is up-to-date
+        if let Some(_chunk) = SnapshotCache::with_snapshot(
+            request.group_identifier,
+            SnapshotKind::Invites,
+            codec,
+            |bytes, hash| DataChunk::from_bytes(bytes, hash, &request, codec),
+        ) {
+            return _chunk;
+        }
+
+        let bytes = serialize(&Self::get_group_invites(request.group_identifier))
+            .map_err(|err| ChunkError::SerializationFailed(err.to_string()))?;
+        let encoded = codec.encode(bytes)?;
+        DataChunk::from_bytes(&encoded, &checksum(&encoded), &request, codec)
+    }
+
+    // The chunk size is capped by the config pushed by the parent canister
+    fn cap_chunk_request(request: ChunkRequest) -> ChunkRequest {
+        ChunkRequest {
+            max_bytes_per_chunk: request
+                .max_bytes_per_chunk
+                .min(Self::get_config().max_bytes_per_chunk),
+            ..request
+        }
+    }
+
     // Used for composite_query calls from the parent canister
     //
     // Method to get filtered members serialized and chunked
@@ -1626,28 +3164,17 @@ impl Store {
         chunk: usize,
         max_bytes_per_chunk: usize,
     ) -> (Vec<u8>, (usize, usize)) {
-        let members = ENTRIES.with(|entries| Data::get_entries(entries));
-        // Get members for filtering
-        let mapped_members: Vec<JoinedMemberResponse> = members
-            .iter()
-            // Filter members that have joined the group
-            .filter(|(_identifier, _member_data)| {
-                _member_data
-                    .joined
-                    .iter()
-                    .any(|(_group_identifier, _)| _group_identifier == group_identifier)
-            })
-            // Map member to joined member response
-            .map(|(_identifier, _group_data)| {
-                Self::map_member_to_joined_member_response(
-                    &Principal::from_text(_identifier).unwrap_or(Principal::anonymous()),
-                    _group_data,
-                    group_identifier.clone(),
-                )
-            })
-            .collect();
+        // Serve the bytes from the cached snapshot of the group when it is up-to-date
+        let serialized = SnapshotCache::with_snapshot(
+            *group_identifier,
+            SnapshotKind::Members,
+            ChunkCodec::Identity,
+            |bytes, _| bytes.to_vec(),
+        )
+        .map(Ok)
+        .unwrap_or_else(|| serialize(&Self::get_group_members(*group_identifier)));
 
-        if let Ok(bytes) = serialize(&mapped_members) {
+        if let Ok(bytes) = serialized {
             // Check if the bytes of the serialized groups are greater than the max bytes per chunk specified as an argument
             if bytes.len() >= max_bytes_per_chunk {
                 // Get the start and end index of the bytes to be returned
@@ -1687,28 +3214,17 @@ impl Store {
         chunk: usize,
         max_bytes_per_chunk: usize,
     ) -> (Vec<u8>, (usize, usize)) {
-        let members = ENTRIES.with(|entries| Data::get_entries(entries));
-        // Get members for filtering
-        let mapped_members: Vec<InviteMemberResponse> = members
-            .iter()
-            // Filter members that have joined the group
-            .filter(|(_identifier, _member_data)| {
-                _member_data
-                    .invites
-                    .iter()
-                    .any(|(_group_identifier, _)| _group_identifier == group_identifier)
-            })
-            // Map member to joined member response
-            .map(|(_identifier, _group_data)| {
-                Self::map_member_to_invite_member_response(
-                    &Principal::from_text(_identifier).unwrap_or(Principal::anonymous()),
-                    _group_data,
-                    group_identifier.clone(),
-                )
-            })
-            .collect();
+        // Serve the bytes from the cached snapshot of the group when it is up-to-date
+        let serialized = SnapshotCache::with_snapshot(
+            *group_identifier,
+            SnapshotKind::Invites,
+            ChunkCodec::Identity,
+            |bytes, _| bytes.to_vec(),
+        )
+        .map(Ok)
+        .unwrap_or_else(|| serialize(&Self::get_group_invites(*group_identifier)));
 
-        if let Ok(bytes) = serialize(&mapped_members) {
+        if let Ok(bytes) = serialized {
             // Check if the bytes of the serialized groups are greater than the max bytes per chunk specified as an argument
             if bytes.len() >= max_bytes_per_chunk {
                 // Get the start and end index of the bytes to be returned
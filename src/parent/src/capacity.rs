@@ -0,0 +1,349 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use candid::Principal;
+use ic_cdk::api::{call, time};
+use ic_scalable_misc::{
+    enums::api_error_type::{ApiError, ApiErrorType},
+    helpers::{error_helper::api_error, logger_helper::add_log},
+    models::logger_models::{LogType, PostLog},
+};
+use ic_stable_structures::StableBTreeMap;
+use shared::{
+    capacity_model::{GroupCapacity, SeatClaim, WaitlistEntry},
+    member_model::MemberEntryResponse,
+};
+
+use crate::store::{Memory, ScalableData, GROUP_CAPACITIES_MEMORY_ID, MEMORY_MANAGER};
+
+thread_local! {
+    // The capacity, taken seats and waitlist of the groups that have a capacity, by group identifier
+    pub static GROUP_CAPACITIES: RefCell<StableBTreeMap<String, GroupCapacity, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_CAPACITIES_MEMORY_ID)),
+        )
+    );
+
+    // The seats that are taken while the members of a group are counted, by group identifier
+    // (amount of counts in progress, seats taken since the first count started)
+    static COUNTING_CLAIMS: RefCell<HashMap<Principal, (u64, u64)>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug)]
+enum PromotionFailure {
+    // The child canister rejected the member
+    Rejected(ApiError),
+    // The child canister could not be reached
+    CallFailed(String),
+}
+
+// The seats of a group are counted on the parent canister so the capacity is enforced across all child canisters
+// the child canisters claim a seat before adding a join and release it when a member leaves or is removed
+pub struct GroupCapacities;
+
+impl GroupCapacities {
+    pub fn get(group_identifier: Principal) -> Option<GroupCapacity> {
+        GROUP_CAPACITIES.with(|c| c.borrow().get(&group_identifier.to_string()))
+    }
+
+    // Method to set or remove the capacity of a group, the waitlist is promoted when seats become available
+    // the taken seats are counted on the child canisters every time the capacity is set, this corrects seats of
+    // members that left while their release could not reach the parent canister
+    // seats that are claimed while the members are counted are added to the count, so they are not lost
+    pub async fn set(
+        group_identifier: Principal,
+        capacity: Option<u64>,
+    ) -> Result<Option<GroupCapacity>, ApiError> {
+        let inputs = Some(vec![
+            format!("group_identifier - {}", group_identifier),
+            format!("capacity - {:?}", capacity),
+        ]);
+
+        let capacity = match capacity {
+            // Without a capacity every member on the waitlist is promoted
+            None => {
+                if let Some(_group_capacity) = Self::get(group_identifier) {
+                    GROUP_CAPACITIES.with(|c| c.borrow_mut().remove(&group_identifier.to_string()));
+
+                    for entry in _group_capacity.waitlist {
+                        let _ = Self::add_waitlisted_member(group_identifier, entry).await;
+                    }
+                }
+                return Ok(None);
+            }
+            Some(0) => {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "INVALID_CAPACITY",
+                    "The capacity should be greater than 0",
+                    &ScalableData::get_name(),
+                    "set_group_capacity",
+                    inputs,
+                ))
+            }
+            Some(_capacity) => _capacity,
+        };
+
+        let claims_before = Self::start_counting(group_identifier);
+        let counted = Self::count_members(group_identifier).await;
+        let claims_during_count = Self::stop_counting(group_identifier) - claims_before;
+
+        // Seats taken during the count can be missing from the count, so these are added to it
+        // a join that is added before its child canister is counted is counted twice, the next count corrects this
+        let members = match counted {
+            Ok(_members) => _members + claims_during_count,
+            Err(reason) => {
+                return Err(api_error(
+                    ApiErrorType::BadRequest,
+                    "MEMBER_COUNT_FAILED",
+                    reason.as_str(),
+                    &ScalableData::get_name(),
+                    "set_group_capacity",
+                    inputs,
+                ))
+            }
+        };
+
+        // The capacity is read after the members are counted, the waitlist can be changed during the count
+        let mut group_capacity = Self::get(group_identifier).unwrap_or(GroupCapacity {
+            group_identifier,
+            capacity,
+            members,
+            waitlist: vec![],
+            updated_at: time(),
+        });
+
+        group_capacity.members = members;
+        group_capacity.capacity = capacity;
+        group_capacity.updated_at = time();
+        Self::store(&group_capacity);
+
+        Self::promote_waitlist(group_identifier).await;
+        Ok(Self::get(group_identifier))
+    }
+
+    // Method to claim a seat for a join, past the capacity the join is added to the waitlist
+    // a principal is added to the waitlist once, a repeated claim returns the current position
+    pub fn claim_seat(
+        group_identifier: Principal,
+        principal: Principal,
+        canister: Principal,
+        role: String,
+    ) -> SeatClaim {
+        let mut group_capacity = match Self::get(group_identifier) {
+            None => {
+                Self::track_claim(group_identifier);
+                return SeatClaim::Granted;
+            }
+            Some(_group_capacity) => _group_capacity,
+        };
+
+        if let Some(_index) = group_capacity
+            .waitlist
+            .iter()
+            .position(|entry| entry.principal == principal)
+        {
+            return SeatClaim::Waitlisted {
+                position: _index as u64 + 1,
+            };
+        }
+
+        let claim = match group_capacity.members < group_capacity.capacity {
+            true => {
+                group_capacity.members += 1;
+                Self::track_claim(group_identifier);
+                SeatClaim::Granted
+            }
+            false => {
+                group_capacity.waitlist.push(WaitlistEntry {
+                    principal,
+                    canister,
+                    role,
+                    created_at: time(),
+                });
+                SeatClaim::Waitlisted {
+                    position: group_capacity.waitlist.len() as u64,
+                }
+            }
+        };
+
+        Self::store(&group_capacity);
+        claim
+    }
+
+    // Method to release a seat when a member leaves or is removed, the first member on the waitlist is promoted
+    pub async fn release_seat(group_identifier: Principal) {
+        let mut group_capacity = match Self::get(group_identifier) {
+            None => return,
+            Some(_group_capacity) => _group_capacity,
+        };
+
+        group_capacity.members = group_capacity.members.saturating_sub(1);
+        Self::store(&group_capacity);
+
+        Self::promote_waitlist(group_identifier).await;
+    }
+
+    // Method to promote the members on the waitlist as long as there are seats available
+    // the seat is taken before the inter-canister call, so claims during the call can not take it
+    async fn promote_waitlist(group_identifier: Principal) {
+        loop {
+            let entry = match Self::get(group_identifier) {
+                Some(mut _group_capacity)
+                    if _group_capacity.members < _group_capacity.capacity
+                        && !_group_capacity.waitlist.is_empty() =>
+                {
+                    let entry = _group_capacity.waitlist.remove(0);
+                    _group_capacity.members += 1;
+                    Self::store(&_group_capacity);
+                    Self::track_claim(group_identifier);
+                    entry
+                }
+                _ => return,
+            };
+
+            match Self::add_waitlisted_member(group_identifier, entry.clone()).await {
+                Ok(_) => {}
+                // The member could not be added (for example because it joined in the meantime), free the seat
+                Err(PromotionFailure::Rejected(_)) => Self::free_seat(group_identifier, None),
+                // The child canister could not be reached, put the member back in front of the waitlist
+                Err(PromotionFailure::CallFailed(_)) => {
+                    Self::free_seat(group_identifier, Some(entry));
+                    return;
+                }
+            }
+        }
+    }
+
+    // Method to remove a member from the waitlist, returns if the member was on the waitlist
+    pub fn remove_from_waitlist(group_identifier: Principal, principal: Principal) -> bool {
+        let mut group_capacity = match Self::get(group_identifier) {
+            None => return false,
+            Some(_group_capacity) => _group_capacity,
+        };
+
+        let length = group_capacity.waitlist.len();
+        group_capacity
+            .waitlist
+            .retain(|entry| entry.principal != principal);
+        if group_capacity.waitlist.len() == length {
+            return false;
+        }
+
+        group_capacity.updated_at = time();
+        Self::store(&group_capacity);
+        true
+    }
+
+    fn free_seat(group_identifier: Principal, entry: Option<WaitlistEntry>) {
+        if let Some(mut _group_capacity) = Self::get(group_identifier) {
+            _group_capacity.members = _group_capacity.members.saturating_sub(1);
+            if let Some(_entry) = entry {
+                _group_capacity.waitlist.insert(0, _entry);
+            }
+            Self::store(&_group_capacity);
+        }
+    }
+
+    // Method to add a member from the waitlist on the child canister that received the join (inter-canister call)
+    async fn add_waitlisted_member(
+        group_identifier: Principal,
+        entry: WaitlistEntry,
+    ) -> Result<MemberEntryResponse, PromotionFailure> {
+        let result: Result<(Result<MemberEntryResponse, ApiError>,), _> = call::call(
+            entry.canister,
+            "add_waitlisted_member",
+            (group_identifier, entry.principal, entry.role.clone()),
+        )
+        .await;
+
+        let result = match result {
+            Err(err) => Err(PromotionFailure::CallFailed(err.1)),
+            Ok((_result,)) => _result.map_err(PromotionFailure::Rejected),
+        };
+
+        if let Err(_err) = &result {
+            let reason = match _err {
+                PromotionFailure::Rejected(err) => format!("rejected: {:?}", err),
+                PromotionFailure::CallFailed(reason) => format!("call failed: {}", reason),
+            };
+            add_log(PostLog {
+                log_type: LogType::Error,
+                description: "Waitlisted member not added".to_string(),
+                source: "promote_waitlist".to_string(),
+                data: format!("{} ({}): {}", entry.principal, group_identifier, reason),
+            });
+        }
+
+        result
+    }
+
+    // Method to count the members of a group on all child canisters
+    async fn count_members(group_identifier: Principal) -> Result<u64, String> {
+        let (counts, failed_children) = ScalableData::fan_out_to_children(|canister| async move {
+            let result: Result<(Vec<(Principal, usize)>,), _> = call::call(
+                canister,
+                "get_group_members_count",
+                (vec![group_identifier],),
+            )
+            .await;
+
+            result
+                .map(|(_counts,)| _counts)
+                .map_err(|(code, message)| format!("{:?}: {}", code, message))
+        })
+        .await;
+
+        match failed_children.first() {
+            Some(_failure) => Err(format!(
+                "The members on {} could not be counted: {}",
+                _failure.canister, _failure.reason
+            )),
+            None => Ok(counts.iter().map(|(_, _count)| *_count as u64).sum()),
+        }
+    }
+
+    // Method to start tracking the seats that are taken during a count, returns the seats taken so far
+    fn start_counting(group_identifier: Principal) -> u64 {
+        COUNTING_CLAIMS.with(|c| {
+            let mut counting = c.borrow_mut();
+            let (counts, claims) = counting.entry(group_identifier).or_insert((0, 0));
+            *counts += 1;
+            *claims
+        })
+    }
+
+    // Method to stop tracking the seats that are taken during a count, returns the seats taken so far
+    fn stop_counting(group_identifier: Principal) -> u64 {
+        COUNTING_CLAIMS.with(|c| {
+            let mut counting = c.borrow_mut();
+            let (counts, claims) = match counting.get_mut(&group_identifier) {
+                None => return 0,
+                Some((_counts, _claims)) => {
+                    *_counts -= 1;
+                    (*_counts, *_claims)
+                }
+            };
+            if counts == 0 {
+                counting.remove(&group_identifier);
+            }
+            claims
+        })
+    }
+
+    fn track_claim(group_identifier: Principal) {
+        COUNTING_CLAIMS.with(|c| {
+            if let Some((_, _claims)) = c.borrow_mut().get_mut(&group_identifier) {
+                *_claims += 1;
+            }
+        });
+    }
+
+    fn store(group_capacity: &GroupCapacity) {
+        GROUP_CAPACITIES.with(|c| {
+            c.borrow_mut().insert(
+                group_capacity.group_identifier.to_string(),
+                group_capacity.clone(),
+            )
+        });
+    }
+}
@@ -0,0 +1,295 @@
+use std::{borrow::Cow, cell::RefCell, convert::TryFrom, time::Duration};
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::{
+    canister_balance,
+    management_canister::main::{canister_status, deposit_cycles, CanisterIdRecord},
+    time,
+};
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+use ic_scalable_misc::{
+    enums::api_error_type::{ApiError, ApiErrorType},
+    helpers::{error_helper::api_error, logger_helper::add_log},
+    models::logger_models::{LogType, PostLog},
+};
+use ic_stable_structures::{storable::Bound, StableBTreeMap, StableCell, Storable};
+
+use crate::store::{
+    Memory, ScalableData, CYCLES_SETTINGS_MEMORY_ID, CYCLES_TOP_UPS_MEMORY_ID, MEMORY_MANAGER,
+};
+
+// The amount of top-up entries that are kept in the history
+static MAX_TOP_UP_ENTRIES: u64 = 1000;
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct CyclesSettings {
+    // A child canister is topped up when its balance falls below this threshold
+    pub min_child_cycles: u64,
+    // The amount of cycles that is deposited per top-up
+    pub top_up_amount: u64,
+    // The amount of cycles the parent canister keeps for itself, no top-ups are done below this balance
+    pub min_parent_cycles: u64,
+    // The seconds between the balance checks
+    pub check_interval_secs: u64,
+}
+
+impl Default for CyclesSettings {
+    fn default() -> Self {
+        CyclesSettings {
+            min_child_cycles: 1_000_000_000_000,
+            top_up_amount: 1_000_000_000_000,
+            min_parent_cycles: 2_000_000_000_000,
+            check_interval_secs: 3600,
+        }
+    }
+}
+
+impl Storable for CyclesSettings {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum CyclesTopUpStatus {
+    Deposited,
+    // The deposit call failed
+    Failed(String),
+    // The parent canister balance is too low to refill the child canister
+    InsufficientParentCycles,
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct CyclesTopUp {
+    pub canister: Principal,
+    pub amount: u64,
+    // The cycles balance of the child canister before the top-up
+    pub child_balance: u64,
+    // The cycles balance of the parent canister before the top-up
+    pub parent_balance: u64,
+    pub status: CyclesTopUpStatus,
+    pub created_at: u64,
+}
+
+impl Storable for CyclesTopUp {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    pub static CYCLES_SETTINGS: RefCell<StableCell<CyclesSettings, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CYCLES_SETTINGS_MEMORY_ID)),
+            CyclesSettings::default(),
+        ).expect("failed")
+    );
+
+    // The history of the top-ups and failed refills, by incrementing id
+    pub static CYCLES_TOP_UPS: RefCell<StableBTreeMap<u64, CyclesTopUp, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CYCLES_TOP_UPS_MEMORY_ID)),
+        )
+    );
+
+    // Not stored because timers do not survive an upgrade
+    static CHECK_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+    static CHECK_RUNNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+pub struct Cycles;
+
+impl Cycles {
+    pub fn get_settings() -> CyclesSettings {
+        CYCLES_SETTINGS.with(|s| s.borrow().get().clone())
+    }
+
+    // Method to validate and store the cycles settings, the check timer is restarted with the new interval
+    pub fn update_settings(settings: CyclesSettings) -> Result<CyclesSettings, ApiError> {
+        if settings.check_interval_secs < 60 {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_INTERVAL",
+                "The check interval should be at least 60 seconds",
+                &ScalableData::get_name(),
+                "update_cycles_settings",
+                Some(vec![format!("settings - {:?}", &settings)]),
+            ));
+        }
+
+        if settings.top_up_amount == 0 {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_TOP_UP_AMOUNT",
+                "The top-up amount should be greater than 0",
+                &ScalableData::get_name(),
+                "update_cycles_settings",
+                Some(vec![format!("settings - {:?}", &settings)]),
+            ));
+        }
+
+        let _ = CYCLES_SETTINGS.with(|s| s.borrow_mut().set(settings.clone()));
+        Self::start_timer();
+        Ok(settings)
+    }
+
+    // Method to get the top-up history, newest first
+    pub fn get_top_ups() -> Vec<CyclesTopUp> {
+        let mut top_ups: Vec<CyclesTopUp> =
+            CYCLES_TOP_UPS.with(|t| t.borrow().iter().map(|(_, top_up)| top_up).collect());
+        top_ups.reverse();
+        top_ups
+    }
+
+    // Method to (re)start the timer that checks the child canister balances, used on init, post_upgrade and settings updates
+    pub fn start_timer() {
+        CHECK_TIMER.with(|t| {
+            if let Some(_timer_id) = t.borrow_mut().take() {
+                clear_timer(_timer_id);
+            }
+        });
+
+        let interval = Duration::from_secs(Self::get_settings().check_interval_secs);
+        let timer_id = set_timer_interval(interval, || ic_cdk::spawn(Self::check_children()));
+        CHECK_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+    }
+
+    // Method to check the balance of every child canister and top up the ones below the threshold
+    pub async fn check_children() {
+        if CHECK_RUNNING.with(|r| *r.borrow()) {
+            return;
+        }
+        CHECK_RUNNING.with(|r| *r.borrow_mut() = true);
+
+        for canister in ScalableData::get_canisters() {
+            Self::check_child(canister.principal).await;
+        }
+
+        CHECK_RUNNING.with(|r| *r.borrow_mut() = false);
+    }
+
+    async fn check_child(canister_principal: Principal) {
+        let settings = Self::get_settings();
+
+        let child_balance = match canister_status(CanisterIdRecord {
+            canister_id: canister_principal,
+        })
+        .await
+        {
+            Ok((_status,)) => u64::try_from(_status.cycles.0).unwrap_or(u64::MAX),
+            Err(err) => {
+                add_log(PostLog {
+                    log_type: LogType::Error,
+                    description: "Child canister status not retrieved".to_string(),
+                    source: "check_children_cycles".to_string(),
+                    data: format!("{}: {}", canister_principal, err.1),
+                });
+                return;
+            }
+        };
+
+        if child_balance >= settings.min_child_cycles {
+            return;
+        }
+
+        let parent_balance = canister_balance();
+        if parent_balance
+            < settings
+                .min_parent_cycles
+                .saturating_add(settings.top_up_amount)
+        {
+            Self::add_top_up(
+                canister_principal,
+                0,
+                child_balance,
+                parent_balance,
+                CyclesTopUpStatus::InsufficientParentCycles,
+            );
+            add_log(PostLog {
+                log_type: LogType::Error,
+                description: "Low cycles balance, child canister can not be refilled".to_string(),
+                source: "check_children_cycles".to_string(),
+                data: format!(
+                    "child: {} ({} cycles), parent: {} cycles",
+                    canister_principal, child_balance, parent_balance
+                ),
+            });
+            return;
+        }
+
+        let status = match deposit_cycles(
+            CanisterIdRecord {
+                canister_id: canister_principal,
+            },
+            settings.top_up_amount as u128,
+        )
+        .await
+        {
+            Ok(_) => CyclesTopUpStatus::Deposited,
+            Err(err) => {
+                add_log(PostLog {
+                    log_type: LogType::Error,
+                    description: "Low cycles balance, child canister top-up failed".to_string(),
+                    source: "check_children_cycles".to_string(),
+                    data: format!("{}: {}", canister_principal, err.1),
+                });
+                CyclesTopUpStatus::Failed(err.1)
+            }
+        };
+
+        let amount = match status {
+            CyclesTopUpStatus::Deposited => settings.top_up_amount,
+            _ => 0,
+        };
+        Self::add_top_up(
+            canister_principal,
+            amount,
+            child_balance,
+            parent_balance,
+            status,
+        );
+    }
+
+    fn add_top_up(
+        canister: Principal,
+        amount: u64,
+        child_balance: u64,
+        parent_balance: u64,
+        status: CyclesTopUpStatus,
+    ) {
+        CYCLES_TOP_UPS.with(|t| {
+            let mut top_ups = t.borrow_mut();
+            let id = top_ups.last_key_value().map(|(id, _)| id + 1).unwrap_or(0);
+            top_ups.insert(
+                id,
+                CyclesTopUp {
+                    canister,
+                    amount,
+                    child_balance,
+                    parent_balance,
+                    status,
+                    created_at: time(),
+                },
+            );
+
+            while top_ups.len() > MAX_TOP_UP_ENTRIES {
+                match top_ups.first_key_value() {
+                    Some((_oldest, _)) => top_ups.remove(&_oldest),
+                    None => break,
+                };
+            }
+        });
+    }
+}
@@ -1,65 +1,82 @@
 use std::time::Duration;
 
-use ic_cdk::{caller, init, post_upgrade, pre_upgrade, query, storage};
+use ic_cdk::{caller, init, post_upgrade, query, storage};
 use ic_cdk_timers::set_timer;
 use ic_scalable_misc::{
     helpers::logger_helper::add_log,
     models::logger_models::{LogType, PostLog},
 };
 
-use super::store::{ScalableData, DATA};
-
-// Stores the data in stable storage before upgrading the canister.
-#[pre_upgrade]
-pub fn pre_upgrade() {
-    DATA.with(|data| storage::stable_save((&*data.borrow(),)))
-        .expect("Something went wrong while upgrading");
-}
+use super::{
+    cycles::Cycles,
+    fleet_metrics::FleetMetrics,
+    rollout::Rollout,
+    store::{ChildWasmSource, LegacyScalableData, ScalableData},
+};
 
-// Restores the data from stable- to heap storage after upgrading the canister.
+// Triggered after upgrading the canister
+// The data is kept in stable structures, so nothing needs to be serialized or restored on upgrade
 #[post_upgrade]
 pub fn post_upgrade() {
-    let (mut old_store,): (ScalableData,) = storage::stable_restore().unwrap();
-    use ic_scalable_misc::enums::wasm_version_type::WasmVersion::*;
-    let version = match old_store.child_wasm_data.wasm_version {
-        Version(_version) => _version + 1,
-        _ => 0,
-    };
+    // One-time migration of the data that was stored with `stable_save` by previous versions
+    // this needs to happen before the stable structures are initialized
+    if ScalableData::has_legacy_stable_data() {
+        let (legacy_store,): (LegacyScalableData,) = storage::stable_restore().unwrap();
+        ScalableData::migrate_legacy_data(legacy_store);
+
+        add_log(PostLog {
+            log_type: LogType::Info,
+            description: "Migrated data to stable structures".to_string(),
+            source: "post_upgrade".to_string(),
+            data: "".to_string(),
+        });
+    }
+
+    // Timers do not survive an upgrade, so restart the child canister cycles check and fleet metrics
+    Cycles::start_timer();
+    FleetMetrics::start_timer();
+
+    let version = ScalableData::get_next_child_wasm_version();
 
-    // Get the child wasm data from the old store
-    let child_wasm_data = ScalableData::get_child_wasm_data(&old_store, version);
+    // Get the child wasm data that is included in this canister
+    let child_wasm_data = ScalableData::get_embedded_child_wasm_data(version);
     match child_wasm_data {
-        // If the child wasm data is found, update the data in the new store
+        // If the child wasm data is found, store it
         Ok(_child_wasm_data) => {
-            DATA.with(|d| {
-                old_store.child_wasm_data = _child_wasm_data;
-                *d.borrow_mut() = old_store;
-            });
+            ScalableData::set_child_wasm_data(_child_wasm_data, ChildWasmSource::Embedded);
 
-            add_log(PostLog {
-                log_type: LogType::Info,
-                description: "canister children upgrading".to_string(),
-                source: "post_upgrade".to_string(),
-                data: "".to_string(),
-            });
+            // Start a staged rollout to upgrade the child canisters, an active rollout is superseded by the new child wasm
+            match Rollout::supersede() {
+                Ok(_) => add_log(PostLog {
+                    log_type: LogType::Info,
+                    description: "canister children rollout started".to_string(),
+                    source: "post_upgrade".to_string(),
+                    data: "".to_string(),
+                }),
+                Err(err) => {
+                    add_log(PostLog {
+                        log_type: LogType::Error,
+                        description: "canister children rollout not started".to_string(),
+                        source: "post_upgrade".to_string(),
+                        data: format!("{:?}", err),
+                    });
 
-            // Use a timer to trigger the upgrade_children method to upgrade the child WASMs
-            set_timer(Duration::from_secs(0), || {
-                ic_cdk::spawn(ScalableData::upgrade_children());
-            });
+                    // Timers do not survive an upgrade, so pick up an active rollout again
+                    Rollout::continue_active();
+                }
+            }
         }
-        // If the child wasm data is not found, continue restoring the old store
+        // If the child wasm data is not found, keep the stored child wasm data
         Err(err) => {
-            DATA.with(|d| {
-                *d.borrow_mut() = old_store;
-            });
-
             add_log(PostLog {
                 log_type: LogType::Info,
                 description: "No child upgrade needed".to_string(),
                 source: "post_upgrade".to_string(),
                 data: format!("Error: {}", err),
             });
+
+            // Timers do not survive an upgrade, so pick up an active rollout again
+            Rollout::continue_active();
         }
     }
 }
@@ -67,18 +84,21 @@ pub fn post_upgrade() {
 // Init methods thats get triggered when the canister is installed
 #[init]
 fn init() {
-    DATA.with(|v| {
-        let mut data = v.borrow_mut();
-        data.name = "member_parent".to_string();
-        data.parent = caller();
-        // Set the child WASM data on first deploy from the file system
-        data.child_wasm_data = ScalableData::get_child_wasm_data(&data, 0_0_1).unwrap();
-    });
+    ScalableData::set_data("member_parent".to_string(), caller());
+    // Set the child WASM data on first deploy from the file system
+    ScalableData::set_child_wasm_data(
+        ScalableData::get_embedded_child_wasm_data(0_0_1).unwrap(),
+        ChildWasmSource::Embedded,
+    );
 
     // Spawn the first child canister
     set_timer(Duration::from_secs(0), || {
         ic_cdk::spawn(ScalableData::initialize_first_child_canister());
     });
+
+    // Start checking the cycles balance of the child canisters and gathering the fleet metrics
+    Cycles::start_timer();
+    FleetMetrics::start_timer();
 }
 
 // Hacky way to expose the candid interface to the outside world
@@ -86,14 +106,22 @@ fn init() {
 pub fn __export_did_tmp_() -> String {
     use candid::{export_service, Principal};
 
+    use crate::cycles::{CyclesSettings, CyclesTopUp};
+    use crate::rollout::{RolloutSettings, RolloutState};
+    use crate::store::{
+        ChildWasmHistoryEntry, ChildrenPagedResponse, MemberSearchResponse, MutualGroupsResponse,
+        ParentConfig, PrincipalGroupsResponse,
+    };
     use ic_cdk::api::management_canister::http_request::HttpResponse;
     use ic_scalable_misc::enums::api_error_type::ApiError;
     use ic_scalable_misc::enums::wasm_version_type::WasmVersion;
     use ic_scalable_misc::models::canister_models::ScalableCanisterDetails;
     use ic_scalable_misc::models::http_models::HttpRequest;
-    use ic_scalable_misc::models::paged_response_models::PagedResponse;
+    use shared::capacity_model::{GroupCapacity, SeatClaim};
+    use shared::invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest};
     use shared::member_model::InviteMemberResponse;
     use shared::member_model::JoinedMemberResponse;
+    use shared::member_model::{Invite, Join};
     export_service!();
     __export_service()
 }
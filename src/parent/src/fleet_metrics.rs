@@ -0,0 +1,203 @@
+use std::{cell::RefCell, convert::TryFrom, time::Duration};
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::{
+    call,
+    management_canister::main::{canister_status, CanisterIdRecord},
+    time,
+};
+use ic_cdk_timers::set_timer_interval;
+use ic_scalable_misc::{
+    enums::wasm_version_type::WasmVersion,
+    helpers::logger_helper::add_log,
+    models::logger_models::{LogType, PostLog},
+};
+use shared::member_model::MemberStats;
+
+use crate::store::ScalableData;
+
+// The seconds between the refreshes of the fleet metrics
+static REFRESH_INTERVAL_SECS: u64 = 300;
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct ChildMetrics {
+    pub canister: Principal,
+    // `None` when the canister status could not be retrieved
+    pub cycles: Option<u64>,
+    pub memory_size: Option<u64>,
+    // `None` when the member stats could not be retrieved
+    pub stats: Option<MemberStats>,
+    pub wasm_version: WasmVersion,
+    pub is_available: bool,
+}
+
+// A gauge as (name, help, value per child canister), values that could not be retrieved are `None`
+type Gauge<'a> = (&'a str, &'a str, Vec<(Principal, Option<u64>)>);
+
+thread_local! {
+    // Gauges of the child canisters, not stored because they are refreshed by the timer after an upgrade
+    static CHILD_METRICS: RefCell<Vec<ChildMetrics>> = const { RefCell::new(vec![]) };
+    static REFRESHED_AT: RefCell<u64> = const { RefCell::new(0) };
+    static REFRESH_RUNNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+pub struct FleetMetrics;
+
+impl FleetMetrics {
+    // Method to start the timer that refreshes the fleet metrics, used on init and post_upgrade
+    pub fn start_timer() {
+        set_timer_interval(Duration::from_secs(REFRESH_INTERVAL_SECS), || {
+            ic_cdk::spawn(Self::refresh())
+        });
+    }
+
+    // Method to gather the gauges of every child canister
+    pub async fn refresh() {
+        if REFRESH_RUNNING.with(|r| *r.borrow()) {
+            return;
+        }
+        REFRESH_RUNNING.with(|r| *r.borrow_mut() = true);
+
+        let mut child_metrics = vec![];
+        for canister in ScalableData::get_canisters() {
+            let (cycles, memory_size) = match canister_status(CanisterIdRecord {
+                canister_id: canister.principal,
+            })
+            .await
+            {
+                Ok((_status,)) => (
+                    Some(u64::try_from(_status.cycles.0).unwrap_or(u64::MAX)),
+                    Some(u64::try_from(_status.memory_size.0).unwrap_or(u64::MAX)),
+                ),
+                Err(err) => {
+                    Self::log_error(canister.principal, "canister_status", err.1);
+                    (None, None)
+                }
+            };
+
+            let stats: Result<(MemberStats,), _> =
+                call::call(canister.principal, "get_member_stats", ()).await;
+            let stats = match stats {
+                Ok((_stats,)) => Some(_stats),
+                Err(err) => {
+                    Self::log_error(canister.principal, "get_member_stats", err.1);
+                    None
+                }
+            };
+
+            child_metrics.push(ChildMetrics {
+                canister: canister.principal,
+                cycles,
+                memory_size,
+                stats,
+                wasm_version: canister.wasm_version,
+                is_available: canister.is_available,
+            });
+        }
+
+        CHILD_METRICS.with(|m| *m.borrow_mut() = child_metrics);
+        REFRESHED_AT.with(|r| *r.borrow_mut() = time());
+        REFRESH_RUNNING.with(|r| *r.borrow_mut() = false);
+    }
+
+    // Method to render the gauges of the child canisters in the Prometheus text format
+    pub fn render() -> String {
+        let child_metrics = CHILD_METRICS.with(|m| m.borrow().clone());
+
+        let gauges: Vec<Gauge> = vec![
+            (
+                "child_cycles",
+                "Cycles balance of the child canister",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, m.cycles))
+                    .collect(),
+            ),
+            (
+                "child_memory_size",
+                "Memory size of the child canister in bytes",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, m.memory_size))
+                    .collect(),
+            ),
+            (
+                "child_entries",
+                "Amount of member entries stored on the child canister",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, m.stats.as_ref().map(|s| s.entries)))
+                    .collect(),
+            ),
+            (
+                "child_joins",
+                "Amount of group joins stored on the child canister",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, m.stats.as_ref().map(|s| s.joins)))
+                    .collect(),
+            ),
+            (
+                "child_invites",
+                "Amount of group invites stored on the child canister",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, m.stats.as_ref().map(|s| s.invites)))
+                    .collect(),
+            ),
+            (
+                "child_wasm_version",
+                "Wasm version that is installed on the child canister, 0 when unknown",
+                child_metrics
+                    .iter()
+                    .map(|m| match m.wasm_version {
+                        WasmVersion::Version(_version) => (m.canister, Some(_version)),
+                        _ => (m.canister, Some(0)),
+                    })
+                    .collect(),
+            ),
+            (
+                "child_available",
+                "1 when the child canister accepts new member entries",
+                child_metrics
+                    .iter()
+                    .map(|m| (m.canister, Some(m.is_available as u64)))
+                    .collect(),
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, help, values) in gauges {
+            output.push_str(&format!("# HELP {} {}\n", name, help));
+            output.push_str(&format!("# TYPE {} gauge\n", name));
+            for (canister, value) in values {
+                // Gauges that could not be retrieved are left out instead of reported as 0
+                if let Some(_value) = value {
+                    output.push_str(&format!(
+                        "{}{{canister=\"{}\"}} {}\n",
+                        name, canister, _value
+                    ));
+                }
+            }
+        }
+
+        output.push_str(
+            "# HELP child_metrics_refreshed_at Timestamp of the last refresh in nanoseconds\n",
+        );
+        output.push_str("# TYPE child_metrics_refreshed_at gauge\n");
+        output.push_str(&format!(
+            "child_metrics_refreshed_at {}\n",
+            REFRESHED_AT.with(|r| *r.borrow())
+        ));
+        output
+    }
+
+    fn log_error(canister_principal: Principal, method_name: &str, message: String) {
+        add_log(PostLog {
+            log_type: LogType::Error,
+            description: format!("Fleet metrics {} failed", method_name),
+            source: "fleet_metrics".to_string(),
+            data: format!("{}: {}", canister_principal, message),
+        });
+    }
+}
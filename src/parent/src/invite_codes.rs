@@ -0,0 +1,290 @@
+use std::{cell::RefCell, cmp::Reverse};
+
+use candid::Principal;
+use ic_cdk::api::{management_canister::main::raw_rand, time};
+use ic_scalable_misc::{
+    enums::api_error_type::{ApiError, ApiErrorType},
+    helpers::error_helper::api_error,
+};
+use ic_stable_structures::StableBTreeMap;
+use shared::invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest};
+
+use crate::store::{
+    Memory, ScalableData, INVITE_CODES_MEMORY_ID, INVITE_CODE_REDEMPTIONS_MEMORY_ID, MEMORY_MANAGER,
+};
+
+// The maximum amount of invite codes per group that are not revoked
+static MAX_INVITE_CODES_PER_GROUP: usize = 100;
+
+// The amount of random bytes a code is generated from
+static INVITE_CODE_BYTES: usize = 12;
+
+thread_local! {
+    // The invite codes of all groups, by code
+    pub static INVITE_CODES: RefCell<StableBTreeMap<String, InviteCode, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INVITE_CODES_MEMORY_ID)),
+        )
+    );
+
+    // The redemptions of the invite codes, by `{code}/{principal}`
+    pub static INVITE_CODE_REDEMPTIONS: RefCell<StableBTreeMap<String, InviteCodeRedemption, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INVITE_CODE_REDEMPTIONS_MEMORY_ID)),
+        )
+    );
+}
+
+// Invite codes are kept on the parent canister so the uses are counted across all child canisters
+// the child canisters check the permissions of the caller before calling these methods
+pub struct InviteCodes;
+
+impl InviteCodes {
+    // Method to create an invite code for a group, the code is generated from the management canister randomness
+    pub async fn create(
+        created_by: Principal,
+        request: InviteCodeRequest,
+    ) -> Result<InviteCode, ApiError> {
+        let inputs = Some(vec![format!("request - {:?}", &request)]);
+
+        let validation_error = if request.max_uses == Some(0) {
+            Some(("INVALID_MAX_USES", "The max uses should be greater than 0"))
+        } else if request
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= time())
+        {
+            Some(("INVALID_EXPIRY", "The code should expire in the future"))
+        } else if request.default_role == Some("owner".to_string()) {
+            Some((
+                "INVALID_ROLE",
+                "The owner role can not be given through an invite code",
+            ))
+        } else if Self::get_active_count(request.group_identifier) >= MAX_INVITE_CODES_PER_GROUP {
+            Some((
+                "TOO_MANY_INVITE_CODES",
+                "The group has too many active invite codes",
+            ))
+        } else {
+            None
+        };
+
+        if let Some((_tag, _message)) = validation_error {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                _tag,
+                _message,
+                &ScalableData::get_name(),
+                "create_invite_code",
+                inputs,
+            ));
+        }
+
+        let code = match raw_rand().await {
+            Ok((_bytes,)) => _bytes
+                .iter()
+                .take(INVITE_CODE_BYTES)
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            Err(err) => {
+                return Err(api_error(
+                    ApiErrorType::Unexpected,
+                    "RANDOMNESS_FAILED",
+                    err.1.as_str(),
+                    &ScalableData::get_name(),
+                    "create_invite_code",
+                    inputs,
+                ))
+            }
+        };
+
+        let invite_code = InviteCode {
+            code: code.clone(),
+            group_identifier: request.group_identifier,
+            created_by,
+            max_uses: request.max_uses,
+            expires_at: request.expires_at,
+            default_role: request.default_role,
+            uses: 0,
+            revoked: false,
+            created_at: time(),
+        };
+
+        INVITE_CODES.with(|c| c.borrow_mut().insert(code, invite_code.clone()));
+        Ok(invite_code)
+    }
+
+    // Method to get the invite codes of a group, including the revoked codes, newest first
+    pub fn get_by_group(group_identifier: Principal) -> Vec<InviteCode> {
+        let mut invite_codes: Vec<InviteCode> = INVITE_CODES.with(|c| {
+            c.borrow()
+                .iter()
+                .map(|(_, invite_code)| invite_code)
+                .filter(|invite_code| invite_code.group_identifier == group_identifier)
+                .collect()
+        });
+        invite_codes.sort_by_key(|invite_code| Reverse(invite_code.created_at));
+        invite_codes
+    }
+
+    // Method to revoke an invite code, the redemptions are kept
+    pub fn revoke(group_identifier: Principal, code: String) -> Result<InviteCode, ApiError> {
+        let mut invite_code = Self::get_group_code(group_identifier, &code, "revoke_invite_code")?;
+
+        invite_code.revoked = true;
+        INVITE_CODES.with(|c| c.borrow_mut().insert(code, invite_code.clone()));
+        Ok(invite_code)
+    }
+
+    // Method to get an invite code, used by the child canisters to check the group before the code is redeemed
+    pub fn get(code: String) -> Result<InviteCode, ApiError> {
+        match INVITE_CODES.with(|c| c.borrow().get(&code)) {
+            None => Err(api_error(
+                ApiErrorType::NotFound,
+                "INVITE_CODE_NOT_FOUND",
+                "The invite code does not exist",
+                &ScalableData::get_name(),
+                "get_invite_code",
+                None,
+            )),
+            Some(_invite_code) => Ok(_invite_code),
+        }
+    }
+
+    // Method to redeem an invite code for a principal, a principal can redeem a code once
+    // the use is counted here, the child canister that stores the member adds the join afterwards
+    pub fn redeem(code: String, principal: Principal) -> Result<InviteCode, ApiError> {
+        let inputs = Some(vec![format!("principal - {}", principal)]);
+        let redemption_key = format!("{}/{}", code, principal);
+
+        let mut invite_code = match INVITE_CODES.with(|c| c.borrow().get(&code)) {
+            None => {
+                return Err(api_error(
+                    ApiErrorType::NotFound,
+                    "INVITE_CODE_NOT_FOUND",
+                    "The invite code does not exist",
+                    &ScalableData::get_name(),
+                    "redeem_invite_code",
+                    inputs,
+                ))
+            }
+            Some(_invite_code) => _invite_code,
+        };
+
+        let redemption_error = if invite_code.revoked {
+            Some(("INVITE_CODE_REVOKED", "The invite code is revoked"))
+        } else if invite_code
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= time())
+        {
+            Some(("INVITE_CODE_EXPIRED", "The invite code is expired"))
+        } else if invite_code
+            .max_uses
+            .is_some_and(|max_uses| invite_code.uses >= max_uses)
+        {
+            Some(("INVITE_CODE_EXHAUSTED", "The invite code has no uses left"))
+        } else if INVITE_CODE_REDEMPTIONS.with(|r| r.borrow().contains_key(&redemption_key)) {
+            Some(("ALREADY_REDEEMED", "You already redeemed this invite code"))
+        } else {
+            None
+        };
+
+        if let Some((_tag, _message)) = redemption_error {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                _tag,
+                _message,
+                &ScalableData::get_name(),
+                "redeem_invite_code",
+                inputs,
+            ));
+        }
+
+        invite_code.uses += 1;
+        INVITE_CODES.with(|c| c.borrow_mut().insert(code, invite_code.clone()));
+        INVITE_CODE_REDEMPTIONS.with(|r| {
+            r.borrow_mut().insert(
+                redemption_key,
+                InviteCodeRedemption {
+                    principal,
+                    redeemed_at: time(),
+                },
+            )
+        });
+        Ok(invite_code)
+    }
+
+    // Method to undo a redemption when the join could not be added, the use is given back to the code
+    pub fn cancel_redemption(code: String, principal: Principal) -> Result<InviteCode, ApiError> {
+        let redemption_key = format!("{}/{}", code, principal);
+
+        let removed = INVITE_CODE_REDEMPTIONS.with(|r| r.borrow_mut().remove(&redemption_key));
+        let mut invite_code = match (removed, INVITE_CODES.with(|c| c.borrow().get(&code))) {
+            (Some(_), Some(_invite_code)) => _invite_code,
+            _ => {
+                return Err(api_error(
+                    ApiErrorType::NotFound,
+                    "REDEMPTION_NOT_FOUND",
+                    "The invite code is not redeemed by this principal",
+                    &ScalableData::get_name(),
+                    "cancel_invite_code_redemption",
+                    Some(vec![format!("principal - {}", principal)]),
+                ))
+            }
+        };
+
+        invite_code.uses = invite_code.uses.saturating_sub(1);
+        INVITE_CODES.with(|c| c.borrow_mut().insert(code, invite_code.clone()));
+        Ok(invite_code)
+    }
+
+    // Method to get the redemptions of an invite code of a group, newest first
+    pub fn get_redemptions(
+        group_identifier: Principal,
+        code: String,
+    ) -> Result<Vec<InviteCodeRedemption>, ApiError> {
+        Self::get_group_code(group_identifier, &code, "get_invite_code_redemptions")?;
+
+        let prefix = format!("{}/", code);
+        let mut redemptions: Vec<InviteCodeRedemption> = INVITE_CODE_REDEMPTIONS.with(|r| {
+            r.borrow()
+                .range(prefix.clone()..)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .map(|(_, redemption)| redemption)
+                .collect()
+        });
+        redemptions.sort_by_key(|redemption| Reverse(redemption.redeemed_at));
+        Ok(redemptions)
+    }
+
+    fn get_active_count(group_identifier: Principal) -> usize {
+        INVITE_CODES.with(|c| {
+            c.borrow()
+                .iter()
+                .filter(|(_, invite_code)| {
+                    invite_code.group_identifier == group_identifier && !invite_code.revoked
+                })
+                .count()
+        })
+    }
+
+    // Method to get an invite code, codes of other groups are reported as not found
+    fn get_group_code(
+        group_identifier: Principal,
+        code: &String,
+        method_name: &str,
+    ) -> Result<InviteCode, ApiError> {
+        match INVITE_CODES.with(|c| c.borrow().get(code)) {
+            Some(_invite_code) if _invite_code.group_identifier == group_identifier => {
+                Ok(_invite_code)
+            }
+            _ => Err(api_error(
+                ApiErrorType::NotFound,
+                "INVITE_CODE_NOT_FOUND",
+                "The invite code does not exist",
+                &ScalableData::get_name(),
+                method_name,
+                Some(vec![format!("group_identifier - {}", group_identifier)]),
+            )),
+        }
+    }
+}
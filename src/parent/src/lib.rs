@@ -1,4 +1,9 @@
+pub mod capacity;
+pub mod cycles;
 pub mod default;
+pub mod fleet_metrics;
+pub mod invite_codes;
 pub mod methods;
+pub mod rollout;
 pub mod scalable_methods;
 pub mod store;
@@ -1,29 +1,182 @@
 use candid::Principal;
-use ic_cdk::query;
-use ic_scalable_misc::models::paged_response_models::PagedResponse;
+use ic_cdk::{caller, query, update};
+use ic_scalable_misc::enums::api_error_type::ApiError;
 
-use shared::member_model::{InviteMemberResponse, JoinedMemberResponse};
+use shared::{
+    capacity_model::{GroupCapacity, SeatClaim},
+    invite_code_model::{InviteCode, InviteCodeRedemption, InviteCodeRequest},
+    member_model::{Invite, InviteMemberResponse, Join, JoinedMemberResponse},
+};
 
-use super::store::ScalableData;
+use super::{
+    capacity::GroupCapacities,
+    invite_codes::InviteCodes,
+    scalable_methods::is_child,
+    store::{
+        ChildrenPagedResponse, MemberSearchResponse, MutualGroupsResponse, PrincipalGroupsResponse,
+        ScalableData,
+    },
+};
 
 // Method used to get all the members from the child canisters filtered, sorted and paged
+// the child canisters are called concurrently, child canisters that could not be queried are reported in `failed_children`
 // requires composite queries to be released to mainnet
 #[query(composite = true)]
 async fn get_members(
     group_identifier: Principal,
     limit: usize,
     page: usize,
-) -> PagedResponse<JoinedMemberResponse> {
+) -> ChildrenPagedResponse<JoinedMemberResponse> {
     ScalableData::get_joined_child_canister_data(group_identifier, limit, page).await
 }
 
 // Method used to get all the members from the child canisters filtered, sorted and paged
+// the child canisters are called concurrently, child canisters that could not be queried are reported in `failed_children`
 // requires composite queries to be released to mainnet
 #[query(composite = true)]
 async fn get_invites(
     group_identifier: Principal,
     limit: usize,
     page: usize,
-) -> PagedResponse<InviteMemberResponse> {
+) -> ChildrenPagedResponse<InviteMemberResponse> {
     ScalableData::get_invites_child_canister_data(group_identifier, limit, page).await
 }
+
+// Method used to search the members of a group on all child canisters by principal, profile identifier or display name
+// the results are ranked by match quality, child canisters that could not be queried are reported in `failed_children`
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn search_group_members(
+    group_identifier: Principal,
+    query: String,
+    limit: usize,
+) -> MemberSearchResponse {
+    ScalableData::search_group_members(group_identifier, query, limit).await
+}
+
+// Method used to get the groups two users both joined, with the roles of each user within the group
+// the members are resolved on whichever child canister they are stored, child canisters that could not be queried are reported in `failed_children`
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_mutual_groups(first: Principal, second: Principal) -> MutualGroupsResponse {
+    ScalableData::get_mutual_groups(first, second).await
+}
+
+// Method used to get the groups a user joined with the roles and timestamps, wherever the member entry is stored
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_groups_for_principal(principal: Principal) -> PrincipalGroupsResponse<Join> {
+    ScalableData::get_groups_for_principal(principal).await
+}
+
+// Method used to get the pending invites of a user with the invite type, wherever the member entry is stored
+// requires composite queries to be released to mainnet
+#[query(composite = true)]
+async fn get_invites_for_principal(principal: Principal) -> PrincipalGroupsResponse<Invite> {
+    ScalableData::get_invites_for_principal(principal).await
+}
+
+// Method used by a child canister to create an invite code for a group (inter-canister call)
+// the child canister checks the permission of the admin that creates the code
+#[update(guard = "is_child")]
+async fn create_invite_code(
+    created_by: Principal,
+    request: InviteCodeRequest,
+) -> Result<InviteCode, ApiError> {
+    InviteCodes::create(created_by, request).await
+}
+
+// Method used by a child canister to get the invite codes of a group, newest first (inter-canister call)
+#[query(guard = "is_child")]
+fn get_invite_codes(group_identifier: Principal) -> Result<Vec<InviteCode>, ApiError> {
+    Ok(InviteCodes::get_by_group(group_identifier))
+}
+
+// Method used by a child canister to revoke an invite code of a group (inter-canister call)
+#[update(guard = "is_child")]
+fn revoke_invite_code(group_identifier: Principal, code: String) -> Result<InviteCode, ApiError> {
+    InviteCodes::revoke(group_identifier, code)
+}
+
+// Method used by a child canister to get an invite code before it is redeemed (inter-canister call)
+#[query(guard = "is_child")]
+fn get_invite_code(code: String) -> Result<InviteCode, ApiError> {
+    InviteCodes::get(code)
+}
+
+// Method used by a child canister to redeem an invite code for a user (inter-canister call)
+// the use is counted across all child canisters, the child canister that stores the member adds the join
+#[update(guard = "is_child")]
+fn redeem_invite_code(code: String, principal: Principal) -> Result<InviteCode, ApiError> {
+    InviteCodes::redeem(code, principal)
+}
+
+// Method used by a child canister to undo a redemption when the join could not be added (inter-canister call)
+#[update(guard = "is_child")]
+fn cancel_invite_code_redemption(
+    code: String,
+    principal: Principal,
+) -> Result<InviteCode, ApiError> {
+    InviteCodes::cancel_redemption(code, principal)
+}
+
+// Method used by a child canister to get the redemptions of an invite code of a group (inter-canister call)
+#[query(guard = "is_child")]
+fn get_invite_code_redemptions(
+    group_identifier: Principal,
+    code: String,
+) -> Result<Vec<InviteCodeRedemption>, ApiError> {
+    InviteCodes::get_redemptions(group_identifier, code)
+}
+
+// Method used by a child canister to set or remove the capacity of a group (inter-canister call)
+// the child canister checks the permission of the admin, the members on the waitlist are promoted when seats become available
+#[update(guard = "is_child")]
+async fn set_group_capacity(
+    group_identifier: Principal,
+    capacity: Option<u64>,
+) -> Result<Option<GroupCapacity>, ApiError> {
+    GroupCapacities::set(group_identifier, capacity).await
+}
+
+// Method used by a child canister to get the capacity, taken seats and waitlist of a group (inter-canister call)
+#[query(guard = "is_child")]
+fn get_group_capacity(group_identifier: Principal) -> Result<Option<GroupCapacity>, ApiError> {
+    Ok(GroupCapacities::get(group_identifier))
+}
+
+// Method used by a child canister to claim a seat before a join is added (inter-canister call)
+// past the capacity the join is added to the waitlist and added on the calling child canister when promoted
+#[update(guard = "is_child")]
+fn claim_group_seat(
+    group_identifier: Principal,
+    principal: Principal,
+    role: String,
+) -> Result<SeatClaim, ApiError> {
+    Ok(GroupCapacities::claim_seat(
+        group_identifier,
+        principal,
+        caller(),
+        role,
+    ))
+}
+
+// Method used by a child canister to release a seat when a member leaves or is removed (inter-canister call)
+#[update(guard = "is_child")]
+async fn release_group_seat(group_identifier: Principal) -> Result<(), ApiError> {
+    GroupCapacities::release_seat(group_identifier).await;
+    Ok(())
+}
+
+// Method used by a child canister to remove a member from the waitlist of a group (inter-canister call)
+// returns if the member was on the waitlist
+#[update(guard = "is_child")]
+fn remove_from_waitlist(
+    group_identifier: Principal,
+    principal: Principal,
+) -> Result<bool, ApiError> {
+    Ok(GroupCapacities::remove_from_waitlist(
+        group_identifier,
+        principal,
+    ))
+}
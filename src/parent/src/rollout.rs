@@ -0,0 +1,423 @@
+use std::{cell::RefCell, time::Duration};
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::{
+    call,
+    management_canister::main::{canister_status, CanisterIdRecord, CanisterStatusType},
+    time,
+};
+use ic_cdk_timers::set_timer;
+use ic_scalable_misc::{
+    enums::{
+        api_error_type::{ApiError, ApiErrorType},
+        wasm_version_type::WasmVersion,
+    },
+    helpers::{error_helper::api_error, logger_helper::add_log},
+    models::logger_models::{LogType, PostLog},
+};
+use ic_stable_structures::StableCell;
+
+use crate::store::{Memory, ScalableData, Stored, MEMORY_MANAGER, ROLLOUT_MEMORY_ID};
+
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum RolloutStatus {
+    // The canary child canister is being upgraded
+    Canary,
+    // The remaining child canisters are being upgraded in batches
+    InProgress,
+    // All child canisters are upgraded
+    Completed,
+    // The rollout is stopped because of a failure or a manual halt, can be resumed
+    Halted,
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct RolloutSettings {
+    // The amount of child canisters that are upgraded per timer tick
+    pub batch_size: u64,
+    // The seconds between the timer ticks
+    pub batch_interval_secs: u64,
+}
+
+impl Default for RolloutSettings {
+    fn default() -> Self {
+        RolloutSettings {
+            batch_size: 5,
+            batch_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct RolloutFailure {
+    pub canister: Principal,
+    pub reason: String,
+    pub failed_at: u64,
+}
+
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct RolloutState {
+    // The child wasm version that is rolled out
+    pub wasm_version: WasmVersion,
+    pub status: RolloutStatus,
+    pub settings: RolloutSettings,
+    // The child canister that is upgraded and checked before the other child canisters
+    pub canary: Option<Principal>,
+    pub pending: Vec<Principal>,
+    pub upgraded: Vec<Principal>,
+    pub failed: Vec<RolloutFailure>,
+    pub halted_reason: Option<String>,
+    pub started_at: u64,
+    pub updated_at: u64,
+}
+
+thread_local! {
+    // The state of the latest child canister rollout
+    pub static ROLLOUT: RefCell<StableCell<Stored<Option<RolloutState>>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLLOUT_MEMORY_ID)),
+            Stored(None),
+        ).expect("failed")
+    );
+
+    // Prevents overlapping timer ticks, not stored because timers do not survive an upgrade
+    static TICK_RUNNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+pub struct Rollout;
+
+impl Rollout {
+    // Method to get the state of the latest rollout
+    pub fn get() -> Option<RolloutState> {
+        ROLLOUT.with(|r| r.borrow().get().0.clone())
+    }
+
+    fn set(state: RolloutState) {
+        let _ = ROLLOUT.with(|r| r.borrow_mut().set(Stored(Some(state))));
+    }
+
+    // Method to start a rollout of the current child wasm to all outdated child canisters
+    // the first outdated child canister is used as canary
+    pub fn start(settings: Option<RolloutSettings>) -> Result<RolloutState, ApiError> {
+        let settings = settings.unwrap_or_default();
+        if settings.batch_size == 0 {
+            return Err(Self::error(
+                "INVALID_BATCH_SIZE",
+                "The batch size should be greater than 0",
+                "start",
+            ));
+        }
+
+        if let Some(_state) = Self::get() {
+            if Self::is_active(&_state) {
+                return Err(Self::error(
+                    "ROLLOUT_IN_PROGRESS",
+                    "There is already a rollout in progress, halt it first",
+                    "start",
+                ));
+            }
+        }
+
+        Self::check_no_tick_running("start")?;
+
+        let wasm_version = ScalableData::get_child_wasm_data().wasm_version;
+        let mut pending: Vec<Principal> = ScalableData::get_canisters()
+            .into_iter()
+            .filter(|c| c.wasm_version != wasm_version)
+            .map(|c| c.principal)
+            .collect();
+        pending.sort();
+
+        let canary = match pending.is_empty() {
+            true => None,
+            false => Some(pending.remove(0)),
+        };
+
+        let state = RolloutState {
+            wasm_version,
+            status: match canary {
+                None => RolloutStatus::Completed,
+                Some(_) => RolloutStatus::Canary,
+            },
+            settings,
+            canary,
+            pending,
+            upgraded: vec![],
+            failed: vec![],
+            halted_reason: None,
+            started_at: time(),
+            updated_at: time(),
+        };
+
+        Self::set(state.clone());
+        Self::schedule_tick(0);
+        Ok(state)
+    }
+
+    // Method to replace an active rollout by a rollout of the current child wasm, the settings of the active rollout are kept
+    // used when the child wasm changes on an upgrade of this canister
+    pub fn supersede() -> Result<RolloutState, ApiError> {
+        let settings = match Self::get() {
+            Some(mut _state) if Self::is_active(&_state) => {
+                _state.status = RolloutStatus::Halted;
+                _state.halted_reason = Some("Superseded by a new child wasm".to_string());
+                _state.updated_at = time();
+                Self::set(_state.clone());
+                Some(_state.settings)
+            }
+            _ => None,
+        };
+
+        Self::start(settings)
+    }
+
+    // Method to manually halt the rollout
+    pub fn halt(reason: String) -> Result<RolloutState, ApiError> {
+        match Self::get() {
+            Some(mut _state) if Self::is_active(&_state) => {
+                _state.status = RolloutStatus::Halted;
+                _state.halted_reason = Some(reason);
+                _state.updated_at = time();
+                Self::set(_state.clone());
+                Ok(_state)
+            }
+            _ => Err(Self::error(
+                "NO_ACTIVE_ROLLOUT",
+                "There is no active rollout",
+                "halt",
+            )),
+        }
+    }
+
+    // Method to resume a halted rollout, the failed child canisters are retried first
+    pub fn resume() -> Result<RolloutState, ApiError> {
+        match Self::get() {
+            Some(mut _state) if _state.status == RolloutStatus::Halted => {
+                if _state.wasm_version != ScalableData::get_child_wasm_data().wasm_version {
+                    return Err(Self::error(
+                        "ROLLOUT_OUTDATED",
+                        "The child wasm changed since this rollout was started, start a new rollout",
+                        "resume",
+                    ));
+                }
+
+                Self::check_no_tick_running("resume")?;
+
+                let mut retry: Vec<Principal> = _state.failed.iter().map(|f| f.canister).collect();
+
+                // If the canary never got upgraded the rollout starts over with the canary
+                match _state.canary {
+                    Some(_canary)
+                        if retry.contains(&_canary) || !_state.upgraded.contains(&_canary) =>
+                    {
+                        retry.retain(|c| c != &_canary);
+                        _state.status = RolloutStatus::Canary;
+                    }
+                    _ => _state.status = RolloutStatus::InProgress,
+                }
+
+                retry.append(&mut _state.pending);
+                _state.pending = retry;
+                _state.failed = vec![];
+                _state.halted_reason = None;
+                _state.updated_at = time();
+
+                Self::set(_state.clone());
+                Self::schedule_tick(0);
+                Ok(_state)
+            }
+            _ => Err(Self::error(
+                "NO_HALTED_ROLLOUT",
+                "There is no halted rollout to resume",
+                "resume",
+            )),
+        }
+    }
+
+    // Method to continue an active rollout after this canister is upgraded, as timers do not survive an upgrade
+    pub fn continue_active() {
+        if let Some(_state) = Self::get() {
+            if Self::is_active(&_state) {
+                Self::schedule_tick(0);
+            }
+        }
+    }
+
+    // A tick that is still awaiting an upgrade stores its progress afterwards, which would overwrite a new or resumed rollout
+    fn check_no_tick_running(method_name: &str) -> Result<(), ApiError> {
+        match TICK_RUNNING.with(|t| *t.borrow()) {
+            true => Err(Self::error(
+                "TICK_RUNNING",
+                "A batch of the halted rollout is still being upgraded, try again later",
+                method_name,
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn is_active(state: &RolloutState) -> bool {
+        state.status == RolloutStatus::Canary || state.status == RolloutStatus::InProgress
+    }
+
+    fn schedule_tick(delay_secs: u64) {
+        set_timer(Duration::from_secs(delay_secs), || {
+            ic_cdk::spawn(Self::tick());
+        });
+    }
+
+    // Method that is triggered by the timer, upgrades the canary or the next batch of child canisters
+    async fn tick() {
+        if TICK_RUNNING.with(|t| *t.borrow()) {
+            return;
+        }
+        TICK_RUNNING.with(|t| *t.borrow_mut() = true);
+
+        if let Some(_state) = Self::get() {
+            match _state.status {
+                RolloutStatus::Canary => Self::upgrade_canary(_state).await,
+                RolloutStatus::InProgress => Self::upgrade_batch(_state).await,
+                _ => {}
+            }
+        }
+
+        TICK_RUNNING.with(|t| *t.borrow_mut() = false);
+    }
+
+    async fn upgrade_canary(mut state: RolloutState) {
+        let canary = match state.canary {
+            Some(_canary) => _canary,
+            None => return,
+        };
+
+        match Self::upgrade_and_check(canary).await {
+            Ok(_) => {
+                state.upgraded.push(canary);
+                state.status = match state.pending.is_empty() {
+                    true => RolloutStatus::Completed,
+                    false => RolloutStatus::InProgress,
+                };
+                Self::save_progress(&mut state);
+
+                Self::log(LogType::Info, "Canary child canister upgraded", &state);
+                if state.status == RolloutStatus::InProgress {
+                    Self::schedule_tick(state.settings.batch_interval_secs);
+                }
+            }
+            Err(err) => Self::halt_on_failure(state, canary, err),
+        }
+    }
+
+    async fn upgrade_batch(mut state: RolloutState) {
+        let batch_size = (state.settings.batch_size as usize).min(state.pending.len());
+
+        for _ in 0..batch_size {
+            let canister = state.pending.remove(0);
+            match Self::upgrade_and_check(canister).await {
+                Ok(_) => {
+                    state.upgraded.push(canister);
+                    Self::save_progress(&mut state);
+                    // Stop when the rollout is halted manually in the meantime
+                    if state.status == RolloutStatus::Halted {
+                        return;
+                    }
+                }
+                Err(err) => return Self::halt_on_failure(state, canister, err),
+            }
+        }
+
+        if state.pending.is_empty() {
+            state.status = RolloutStatus::Completed;
+            Self::save_progress(&mut state);
+            Self::log(LogType::Info, "Child canister rollout completed", &state);
+        } else {
+            Self::schedule_tick(state.settings.batch_interval_secs);
+        }
+    }
+
+    // Method to store the progress of a tick, a manual halt that happened while awaiting an upgrade is kept
+    fn save_progress(state: &mut RolloutState) {
+        if let Some(_current) = Self::get() {
+            if _current.status == RolloutStatus::Halted {
+                state.status = RolloutStatus::Halted;
+                state.halted_reason = _current.halted_reason;
+            }
+        }
+        state.updated_at = time();
+        Self::set(state.clone());
+    }
+
+    fn halt_on_failure(mut state: RolloutState, canister: Principal, reason: String) {
+        state.failed.push(RolloutFailure {
+            canister,
+            reason: reason.clone(),
+            failed_at: time(),
+        });
+        state.status = RolloutStatus::Halted;
+        state.halted_reason = Some(format!("{} failed: {}", canister, reason));
+        state.updated_at = time();
+        Self::set(state.clone());
+
+        Self::log(LogType::Error, "Child canister rollout halted", &state);
+    }
+
+    // Method to upgrade a single child canister and check its health afterwards
+    // the upgrade is skipped when the installed module hash already matches the child wasm
+    async fn upgrade_and_check(canister_principal: Principal) -> Result<(), String> {
+        if let Err(err) = ScalableData::upgrade_child_canister(canister_principal).await {
+            return Err(format!("Upgrade failed: {:?}", err));
+        }
+
+        Self::health_check(canister_principal).await
+    }
+
+    // Method to check if the child canister is running and passes its self test
+    pub async fn health_check(canister_principal: Principal) -> Result<(), String> {
+        match canister_status(CanisterIdRecord {
+            canister_id: canister_principal,
+        })
+        .await
+        {
+            Err(err) => return Err(format!("Canister status failed: {}", err.1)),
+            Ok((_status,)) => {
+                if _status.status != CanisterStatusType::Running {
+                    return Err(format!("Canister is not running: {:?}", _status.status));
+                }
+            }
+        }
+
+        let self_test: Result<(Result<(), String>,), _> =
+            call::call(canister_principal, "self_test", ()).await;
+
+        match self_test {
+            Err(err) => Err(format!("Self test call failed: {}", err.1)),
+            Ok((Err(err),)) => Err(format!("Self test failed: {}", err)),
+            Ok((Ok(_),)) => Ok(()),
+        }
+    }
+
+    fn log(log_type: LogType, description: &str, state: &RolloutState) {
+        add_log(PostLog {
+            log_type,
+            description: description.to_string(),
+            source: "rollout".to_string(),
+            data: format!(
+                "version: {:?}, upgraded: {}, pending: {}, halted_reason: {:?}",
+                state.wasm_version,
+                state.upgraded.len(),
+                state.pending.len(),
+                state.halted_reason
+            ),
+        });
+    }
+
+    fn error(tag: &str, message: &str, method_name: &str) -> ApiError {
+        api_error(
+            ApiErrorType::BadRequest,
+            tag,
+            message,
+            &ScalableData::get_name(),
+            method_name,
+            None,
+        )
+    }
+}
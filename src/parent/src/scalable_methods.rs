@@ -1,5 +1,5 @@
 use candid::Principal;
-use ic_cdk::{caller, query, update};
+use ic_cdk::{api::is_controller, caller, query, update};
 use ic_scalable_misc::{
     enums::{api_error_type::ApiError, wasm_version_type::WasmVersion},
     helpers::{
@@ -12,7 +12,12 @@ use ic_scalable_misc::{
     },
 };
 
-use super::store::{ScalableData, DATA};
+use super::{
+    cycles::{Cycles, CyclesSettings, CyclesTopUp},
+    fleet_metrics::FleetMetrics,
+    rollout::{Rollout, RolloutSettings, RolloutState},
+    store::{ChildWasmHistoryEntry, ParentConfig, ScalableData},
+};
 
 // Method to retrieve an available canister to write updated to
 #[query]
@@ -39,11 +44,11 @@ async fn close_child_canister_and_spawn_sibling(
 // Method to retrieve the latest wasm version of the child canister that is currently stored
 #[query]
 fn get_latest_wasm_version() -> WasmVersion {
-    DATA.with(|v| v.borrow().child_wasm_data.wasm_version.clone())
+    ScalableData::get_child_wasm_data().wasm_version
 }
 
 // HTTP request handler
-// canister metrics are added to the response
+// canister metrics and the metrics of the child canisters are added to the response
 #[query]
 fn http_request(req: HttpRequest) -> HttpResponse {
     let path_entries = vec![PathEntry {
@@ -54,7 +59,10 @@ fn http_request(req: HttpRequest) -> HttpResponse {
                 "content-type".to_string(),
                 "text/plain".to_string(),
             )],
-            body: metrics(vec![]).as_bytes().to_vec(),
+            // The parent metrics are followed by the gauges of the child canisters
+            body: format!("{}\n{}", metrics(vec![]), FleetMetrics::render())
+                .as_bytes()
+                .to_vec(),
         },
     }];
 
@@ -66,3 +74,159 @@ fn http_request(req: HttpRequest) -> HttpResponse {
 fn accept_cycles() -> u64 {
     Canister::accept_cycles()
 }
+
+// Method to retrieve the runtime configuration of this canister
+#[query]
+fn get_config() -> ParentConfig {
+    ScalableData::get_config()
+}
+
+// Method to update the runtime configuration of this canister
+// the child config is pushed to all the child canisters
+#[update(guard = "is_governance")]
+fn update_config(config: ParentConfig) -> Result<ParentConfig, ApiError> {
+    ScalableData::update_config(config)
+}
+
+// Method to upload a new child wasm, the child canisters are upgraded afterwards
+// meant to be called through a SNS generic proposal, the expected hash is the SHA-256 hash of the wasm
+#[update(guard = "is_governance")]
+fn upload_child_wasm(bytes: Vec<u8>, expected_hash: Vec<u8>) -> Result<WasmVersion, ApiError> {
+    ScalableData::upload_child_wasm(bytes, expected_hash)
+}
+
+// Validation method for the `upload_child_wasm` SNS generic proposal
+#[query]
+fn validate_upload_child_wasm(bytes: Vec<u8>, expected_hash: Vec<u8>) -> Result<String, String> {
+    ScalableData::validate_upload_child_wasm(&bytes, &expected_hash)
+}
+
+// Method to upload a chunk of a child wasm that is too large for a single proposal
+// returns the total amount of bytes that are uploaded
+#[update(guard = "is_wasm_uploader")]
+fn upload_child_wasm_chunk(chunk: Vec<u8>) -> u64 {
+    ScalableData::upload_child_wasm_chunk(chunk)
+}
+
+// Method to remove the uploaded child wasm chunks
+#[update(guard = "is_wasm_uploader")]
+fn clear_child_wasm_upload() {
+    ScalableData::clear_child_wasm_upload()
+}
+
+// Method to store the uploaded child wasm chunks as the new child wasm, the child canisters are upgraded afterwards
+// meant to be called through a SNS generic proposal, the expected hash is the SHA-256 hash of the complete wasm
+#[update(guard = "is_governance")]
+fn finalize_child_wasm_upload(expected_hash: Vec<u8>) -> Result<WasmVersion, ApiError> {
+    ScalableData::finalize_child_wasm_upload(expected_hash)
+}
+
+// Validation method for the `finalize_child_wasm_upload` SNS generic proposal
+#[query]
+fn validate_finalize_child_wasm_upload(expected_hash: Vec<u8>) -> Result<String, String> {
+    ScalableData::validate_finalize_child_wasm_upload(&expected_hash)
+}
+
+// Method to retrieve the version history of the child wasm
+#[query]
+fn get_child_wasm_history() -> Vec<ChildWasmHistoryEntry> {
+    ScalableData::get_child_wasm_history()
+}
+
+// Method to retrieve the SHA-256 hash of the module that is installed on the child canisters
+#[query]
+fn get_child_module_hashes() -> Vec<(Principal, Vec<u8>)> {
+    ScalableData::get_child_module_hashes()
+}
+
+// Method to retrieve the versions of the child wasm that can be rolled back to, newest first
+#[query]
+fn get_child_wasm_rollback_versions() -> Vec<WasmVersion> {
+    ScalableData::get_child_wasm_rollback_versions()
+}
+
+// Method to roll back all the child canisters to a previous child wasm
+#[update(guard = "is_governance")]
+async fn rollback_children(
+    to_version: WasmVersion,
+) -> Result<Vec<Result<ScalableCanisterDetails, ApiError>>, ApiError> {
+    ScalableData::rollback_children(to_version).await
+}
+
+// Method to roll back a single child canister to a previous child wasm
+#[update(guard = "is_governance")]
+async fn rollback_child_canister(
+    canister_principal: Principal,
+    to_version: WasmVersion,
+) -> Result<ScalableCanisterDetails, ApiError> {
+    ScalableData::rollback_child_canister(canister_principal, to_version).await
+}
+
+// Method to retrieve the state of the latest child canister rollout
+#[query]
+fn get_child_rollout() -> Option<RolloutState> {
+    Rollout::get()
+}
+
+// Method to start a staged rollout of the current child wasm to the outdated child canisters
+#[update(guard = "is_governance")]
+fn start_child_rollout(settings: Option<RolloutSettings>) -> Result<RolloutState, ApiError> {
+    Rollout::start(settings)
+}
+
+// Method to halt the active child canister rollout
+#[update(guard = "is_governance")]
+fn halt_child_rollout(reason: String) -> Result<RolloutState, ApiError> {
+    Rollout::halt(reason)
+}
+
+// Method to resume a halted child canister rollout, the failed child canisters are retried first
+#[update(guard = "is_governance")]
+fn resume_child_rollout() -> Result<RolloutState, ApiError> {
+    Rollout::resume()
+}
+
+// Method to retrieve the settings for the automatic cycles top-up of the child canisters
+#[query]
+fn get_cycles_settings() -> CyclesSettings {
+    Cycles::get_settings()
+}
+
+// Method to update the settings for the automatic cycles top-up of the child canisters
+#[update(guard = "is_governance")]
+fn update_cycles_settings(settings: CyclesSettings) -> Result<CyclesSettings, ApiError> {
+    Cycles::update_settings(settings)
+}
+
+// Method to retrieve the cycles top-up history of the child canisters, newest first
+#[query]
+fn get_cycles_top_ups() -> Vec<CyclesTopUp> {
+    Cycles::get_top_ups()
+}
+
+// Guard to only allow the SNS governance canister to call a method
+// as long as no governance canister is configured, the controllers of this canister are allowed
+pub fn is_governance() -> Result<(), String> {
+    match ScalableData::get_config().governance {
+        Some(governance) if governance == caller() => Ok(()),
+        None if is_controller(&caller()) => Ok(()),
+        _ => Err("Unauthorized".to_string()),
+    }
+}
+
+// Guard to allow the SNS governance canister and the controllers of this canister to upload child wasm chunks
+// the uploaded chunks are only used after a governance call with the expected hash
+pub fn is_wasm_uploader() -> Result<(), String> {
+    match is_controller(&caller()) {
+        true => Ok(()),
+        false => is_governance(),
+    }
+}
+
+// Guard to only allow the child canisters of this canister to call a method
+pub fn is_child() -> Result<(), String> {
+    match ScalableData::get_canister(&caller()) {
+        Some(_) => Ok(()),
+        None => Err("Unauthorized".to_string()),
+    }
+}
@@ -1,10 +1,17 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, future::Future, time::Duration};
 
-use candid::{CandidType, Deserialize, Principal};
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use futures::future::join_all;
 use ic_cdk::{
-    api::{call, time},
+    api::{
+        call,
+        management_canister::main::{canister_status, CanisterIdRecord},
+        stable::{stable64_read, stable64_size},
+        time,
+    },
     id,
 };
+use ic_cdk_timers::set_timer;
 
 use ic_scalable_misc::{
     enums::{
@@ -26,7 +33,24 @@ use ic_scalable_misc::{
         wasm_models::WasmDetails,
     },
 };
-use shared::member_model::{InviteMemberResponse, JoinedMemberResponse};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager, VirtualMemory},
+    storable::Bound,
+    DefaultMemoryImpl, StableBTreeMap, StableCell, Storable,
+};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use shared::{
+    chunk_model::{
+        assemble_chunks, ChunkCodec, ChunkError, ChunkRequest, DataChunk, CHUNK_PROTOCOL_VERSION,
+    },
+    member_model::{
+        ChildConfig, Invite, InviteMemberResponse, Join, JoinedMemberResponse, Member,
+        MemberSearchResult,
+    },
+};
+
+use crate::rollout::Rollout;
 
 #[derive(CandidType, Clone, Deserialize)]
 pub struct ScalableMetaData {
@@ -41,50 +65,290 @@ pub struct ScalableMetaData {
     pub created_at: u64,
 }
 
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct ParentConfig {
+    // The SNS governance canister that is allowed to update the config
+    pub governance: Option<Principal>,
+    // The amount of cycles a new child canister is created with
+    pub child_canister_cycles: u64,
+    // Additional controllers of the child canisters, the parent canister is always added as controller
+    pub child_canister_controllers: Vec<Principal>,
+    // Default max bytes per chunk when fetching data from the child canisters
+    pub max_bytes_per_chunk: u64,
+    // The configuration that is pushed to the child canisters
+    pub child_config: ChildConfig,
+}
+
+impl Storable for ParentConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Default for ParentConfig {
+    fn default() -> Self {
+        ParentConfig {
+            governance: None,
+            child_canister_cycles: 2_000_000_000_000,
+            child_canister_controllers: vec![],
+            max_bytes_per_chunk: 2_000_000,
+            child_config: ChildConfig::default(),
+        }
+    }
+}
+
 #[derive(CandidType, Clone, Deserialize)]
-pub struct ScalableData {
+pub struct ParentData {
     // The name of the scalable canister (ex; users)
     pub name: String,
-    // The child canisters that are used for storing the scalable data
-    pub canisters: HashMap<Principal, ScalableCanisterDetails>,
     // The parent canister
     pub parent: Principal,
-    // The wasm details that need to be installed on the child canisters
-    pub child_wasm_data: WasmDetails,
     // updated_at record
     pub updated_at: u64,
     // created_at record
     pub created_at: u64,
 }
 
-impl Default for ScalableData {
+impl Storable for ParentData {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Default for ParentData {
     fn default() -> Self {
-        ScalableData {
-            canisters: HashMap::new(),
+        ParentData {
             name: String::default(),
-            child_wasm_data: Default::default(),
             parent: Principal::anonymous(),
-            updated_at: time(),
-            created_at: time(),
+            updated_at: 0,
+            created_at: 0,
         }
     }
 }
 
+// Wrapper to keep types that are defined outside of this crate (ex; `ScalableCanisterDetails`) in stable memory
+#[derive(Clone)]
+pub struct Stored<T>(pub T);
+
+impl<T: CandidType + DeserializeOwned> Storable for Stored<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(&self.0).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Stored(Decode!(bytes.as_ref(), T).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// The data layout that was stored with `stable_save` by previous versions of this canister
+// only used to migrate the data to the stable structures
+#[derive(CandidType, Clone, Deserialize)]
+pub struct LegacyScalableData {
+    pub name: String,
+    pub canisters: HashMap<Principal, ScalableCanisterDetails>,
+    pub parent: Principal,
+    pub child_wasm_data: WasmDetails,
+    pub config: Option<ParentConfig>,
+    pub updated_at: u64,
+    pub created_at: u64,
+}
+
+// The source of a child wasm that is stored on this canister
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum ChildWasmSource {
+    // The wasm that is included in the parent canister wasm
+    Embedded,
+    // The wasm that is uploaded through a (SNS) proposal
+    Upload,
+}
+
+// Entry in the version history of the child wasm
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct ChildWasmHistoryEntry {
+    pub wasm_version: WasmVersion,
+    // SHA-256 hash of the wasm bytes
+    pub hash: Vec<u8>,
+    pub size: u64,
+    pub source: ChildWasmSource,
+    pub created_at: u64,
+}
+
+impl Storable for ChildWasmHistoryEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A child canister that could not be queried while fanning out a call to the child canisters
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct ChildCallFailure {
+    pub canister: Principal,
+    pub reason: String,
+}
+
+// Paged data merged from all child canisters, the child canisters that failed are reported instead of left out silently
+#[derive(CandidType, Deserialize)]
+pub struct ChildrenPagedResponse<T> {
+    pub data: PagedResponse<T>,
+    pub failed_children: Vec<ChildCallFailure>,
+}
+
+// Group members that matched a search query on any child canister, ranked by match quality
+#[derive(CandidType, Clone, Deserialize)]
+pub struct MemberSearchResponse {
+    pub data: Vec<MemberSearchResult>,
+    pub failed_children: Vec<ChildCallFailure>,
+}
+
+// A group both members joined, with the roles of each member within the group
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct MutualGroup {
+    pub group_identifier: Principal,
+    pub first_roles: Vec<String>,
+    pub second_roles: Vec<String>,
+}
+
+#[derive(CandidType, Clone, Deserialize)]
+pub struct MutualGroupsResponse {
+    pub data: Vec<MutualGroup>,
+    pub failed_children: Vec<ChildCallFailure>,
+}
+
+// The joins or invites of a user, gathered from whichever child canister stores the member entry
+#[derive(CandidType, Clone, Deserialize)]
+pub struct PrincipalGroupsResponse<T> {
+    // The child canister that stores the member entry, `None` when no member entry is found
+    pub canister: Option<Principal>,
+    pub member_identifier: Option<Principal>,
+    // (group identifier, join or invite), newest first
+    pub data: Vec<(Principal, T)>,
+    pub failed_children: Vec<ChildCallFailure>,
+}
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+pub static DATA_MEMORY_ID: MemoryId = MemoryId::new(0);
+pub static CANISTERS_MEMORY_ID: MemoryId = MemoryId::new(1);
+pub static CHILD_WASM_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub static CONFIG_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub static CHILD_WASM_UPLOAD_CHUNKS_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub static CHILD_WASM_HISTORY_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub static ROLLOUT_MEMORY_ID: MemoryId = MemoryId::new(6);
+pub static CHILD_WASM_ARCHIVE_MEMORY_ID: MemoryId = MemoryId::new(7);
+pub static CHILD_MODULE_HASHES_MEMORY_ID: MemoryId = MemoryId::new(8);
+pub static CYCLES_SETTINGS_MEMORY_ID: MemoryId = MemoryId::new(9);
+pub static CYCLES_TOP_UPS_MEMORY_ID: MemoryId = MemoryId::new(10);
+pub static INVITE_CODES_MEMORY_ID: MemoryId = MemoryId::new(11);
+pub static INVITE_CODE_REDEMPTIONS_MEMORY_ID: MemoryId = MemoryId::new(12);
+pub static GROUP_CAPACITIES_MEMORY_ID: MemoryId = MemoryId::new(13);
+
+// The amount of previous child wasms that are kept to roll back to
+pub static CHILD_WASM_ARCHIVE_SIZE: u64 = 5;
+
+// The amount of child canisters that are called concurrently when fanning out a call
+pub static MAX_PARALLEL_CHILD_CALLS: usize = 10;
+
+// The amount of times a chunked transfer is started over when the data changes between the chunk calls
+pub static MAX_CHUNK_TRANSFER_ATTEMPTS: usize = 3;
+
+// The maximum amount of results a member search returns
+pub static MAX_SEARCH_RESULTS: usize = 100;
+
 thread_local! {
-    pub static DATA: RefCell<ScalableData> = RefCell::new(ScalableData::default());
+    pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    pub static DATA: RefCell<StableCell<ParentData, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DATA_MEMORY_ID)),
+            ParentData::default(),
+        ).expect("failed")
+    );
+
+    // The child canisters that are used for storing the scalable data
+    pub static CANISTERS: RefCell<StableBTreeMap<String, Stored<ScalableCanisterDetails>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CANISTERS_MEMORY_ID)),
+        )
+    );
+
+    // The wasm details that need to be installed on the child canisters
+    pub static CHILD_WASM: RefCell<StableCell<Stored<WasmDetails>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHILD_WASM_MEMORY_ID)),
+            Stored(WasmDetails::default()),
+        ).expect("failed")
+    );
+
+    // The runtime configuration
+    pub static CONFIG: RefCell<StableCell<ParentConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEMORY_ID)),
+            ParentConfig::default(),
+        ).expect("failed")
+    );
+
+    // The chunks of a child wasm that is being uploaded, by the offset of the chunk in the wasm
+    // every chunk is stored separately so an upload does not rewrite the chunks that are already uploaded
+    pub static CHILD_WASM_UPLOAD_CHUNKS: RefCell<StableBTreeMap<u64, Stored<Vec<u8>>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHILD_WASM_UPLOAD_CHUNKS_MEMORY_ID)),
+        )
+    );
+
+    // The version history of the child wasm, keyed by version
+    pub static CHILD_WASM_HISTORY: RefCell<StableBTreeMap<u64, ChildWasmHistoryEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHILD_WASM_HISTORY_MEMORY_ID)),
+        )
+    );
+
+    // The current and previous child wasms by version, used to roll back the child canisters
+    pub static CHILD_WASM_ARCHIVE: RefCell<StableBTreeMap<u64, Stored<WasmDetails>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHILD_WASM_ARCHIVE_MEMORY_ID)),
+        )
+    );
+
+    // The SHA-256 hash of the module that is installed on the child canisters, by child canister principal
+    // kept next to the `ScalableCanisterDetails` because that type is defined outside of this crate
+    pub static CHILD_MODULE_HASHES: RefCell<StableBTreeMap<String, Stored<Vec<u8>>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHILD_MODULE_HASHES_MEMORY_ID)),
+        )
+    );
 }
+
+pub struct ScalableData;
+
 impl ScalableData {
     // Method to retrieve an available canister to write updates to
     pub fn get_available_canister(caller: Principal) -> Result<ScalableCanisterDetails, String> {
-        let canister = DATA.with(|v| {
-            v.borrow()
-                .canisters
-                .iter()
-                // filter out self in case this method is called by a child canister
-                .filter(|(_, c)| c.principal != caller)
-                .find(|(_, c)| c.is_available)
-                .map(|(_, details)| details.clone())
-        });
+        let canister = Self::get_canisters()
+            .into_iter()
+            // filter out self in case this method is called by a child canister
+            .filter(|c| c.principal != caller)
+            .find(|c| c.is_available);
 
         match canister {
             None => Err("No available canister found".to_string()),
@@ -94,25 +358,82 @@ impl ScalableData {
 
     // Methods to retrieve all the canisters
     pub fn get_canisters() -> Vec<ScalableCanisterDetails> {
-        let canisters: Vec<ScalableCanisterDetails> = DATA.with(|v| {
-            v.borrow()
-                .canisters
+        let canisters: Vec<ScalableCanisterDetails> = CANISTERS.with(|c| {
+            c.borrow()
                 .iter()
-                .map(|(_, details)| details.clone())
+                .map(|(_, details)| details.0.clone())
                 .collect()
         });
         return canisters;
     }
 
+    // Method to retrieve a single child canister
+    pub fn get_canister(canister_principal: &Principal) -> Option<ScalableCanisterDetails> {
+        CANISTERS.with(|c| {
+            c.borrow()
+                .get(&canister_principal.to_string())
+                .map(|details| details.0)
+        })
+    }
+
+    // Method to store the details of a child canister
+    fn insert_canister(details: ScalableCanisterDetails) {
+        CANISTERS.with(|c| {
+            c.borrow_mut()
+                .insert(details.principal.to_string(), Stored(details))
+        });
+    }
+
+    // Method to retrieve the wasm details that need to be installed on the child canisters
+    pub fn get_child_wasm_data() -> WasmDetails {
+        CHILD_WASM.with(|w| w.borrow().get().0.clone())
+    }
+
+    // Method to store the wasm details that need to be installed on the child canisters
+    // the wasm is added to the version history and the archive
+    pub fn set_child_wasm_data(details: WasmDetails, source: ChildWasmSource) {
+        // Archive the outgoing wasm as well, in case it was stored before the archive existed
+        Self::archive_child_wasm(Self::get_child_wasm_data());
+        Self::archive_child_wasm(details.clone());
+
+        if let WasmVersion::Version(_version) = details.wasm_version {
+            CHILD_WASM_HISTORY.with(|h| {
+                h.borrow_mut().insert(
+                    _version,
+                    ChildWasmHistoryEntry {
+                        wasm_version: details.wasm_version.clone(),
+                        hash: Self::hash(&details.bytes),
+                        size: details.bytes.len() as u64,
+                        source,
+                        created_at: time(),
+                    },
+                )
+            });
+        }
+        let _ = CHILD_WASM.with(|w| w.borrow_mut().set(Stored(details)));
+    }
+
+    // Method to store the name and parent of this canister, used on the init function
+    pub fn set_data(name: String, parent: Principal) {
+        let _ = DATA.with(|d| {
+            d.borrow_mut().set(ParentData {
+                name,
+                parent,
+                updated_at: time(),
+                created_at: time(),
+            })
+        });
+    }
+
     // Method used on the init function to spawn a child canister when the parent canister is installed
     pub async fn initialize_first_child_canister() -> () {
         // check if the child wasm is present
-        if DATA.with(|data| data.borrow().child_wasm_data.bytes.len()) == 0 {
+        if CHILD_WASM.with(|w| w.borrow().get().0.bytes.is_empty()) {
             return;
         }
 
         // check if there is already a child canister
-        if DATA.with(|v| v.borrow().canisters.len() != 0) {
+        if CANISTERS.with(|c| !c.borrow().is_empty()) {
             return;
         }
 
@@ -145,7 +466,7 @@ impl ScalableData {
         let inputs = Some(vec![format!("last_entry_id - {:?}", &last_entry_id)]);
 
         // check if the child wasm is present
-        if DATA.with(|v| v.borrow().child_wasm_data.bytes.len() == 0) {
+        if CHILD_WASM.with(|w| w.borrow().get().0.bytes.is_empty()) {
             return Err(api_error(
                 ApiErrorType::BadRequest,
                 "NO_WASM_SPECIFIED",
@@ -157,7 +478,7 @@ impl ScalableData {
         }
 
         // check if the caller is known to this canister
-        let caller_canister = DATA.with(|v| v.borrow().canisters.get(&caller).cloned());
+        let caller_canister = Self::get_canister(&caller);
         match caller_canister {
             None => Err(api_error(
                 ApiErrorType::BadRequest,
@@ -187,11 +508,7 @@ impl ScalableData {
                                 _caller_canister.is_available = false;
                                 _caller_canister.entry_range = (0, Some(last_entry_id));
 
-                                DATA.with(|v| {
-                                    v.borrow_mut()
-                                        .canisters
-                                        .insert(_caller_canister.principal, _caller_canister)
-                                });
+                                Self::insert_canister(_caller_canister);
 
                                 // send the entry to the new canister
                                 let call_result: Result<(Result<(), ApiError>,), _> = call::call(
@@ -223,43 +540,51 @@ impl ScalableData {
     // Method used to upgrade the child canister
     pub async fn upgrade_child_canister(
         canister_principal: Principal,
+    ) -> Result<ScalableCanisterDetails, ApiError> {
+        Self::install_child_wasm(
+            canister_principal,
+            Self::get_child_wasm_data(),
+            "upgrade_scalable_canister",
+        )
+        .await
+    }
+
+    // Method used to install a child wasm on an existing child canister in upgrade mode
+    async fn install_child_wasm(
+        canister_principal: Principal,
+        child_wasm_data: WasmDetails,
+        method_name: &str,
     ) -> Result<ScalableCanisterDetails, ApiError> {
         let inputs = Some(vec![format!(
             "canister_principal - {}",
             &canister_principal.to_string()
         )]);
 
-        let data = DATA.with(|v| v.borrow().clone());
-        match data.canisters.get(&canister_principal).cloned() {
+        match Self::get_canister(&canister_principal) {
             None => Err(api_error(
                 ApiErrorType::NotFound,
                 "NO_CHILDREN",
                 "There are no child canisters found",
                 &Self::get_name(),
-                "upgrade_scalable_canister",
+                method_name,
                 inputs,
             )),
             Some(mut _child_canister) => {
-                // check if the version of the wasm is different then the new version
-                if &data.child_wasm_data.wasm_version == &_child_canister.wasm_version {
-                    return Err(api_error(
-                        ApiErrorType::BadRequest,
-                        "CANISTER_UP_TO_DATE",
-                        "The latest WASM version is already installed",
-                        &Self::get_name(),
-                        "upgrade_scalable_canister",
-                        inputs,
-                    ));
+                // check if the installed module is different then the new module, the child canister is not touched otherwise
+                let hash = Self::hash(&child_wasm_data.bytes);
+                if Self::get_installed_module_hash(canister_principal).await == Some(hash.clone()) {
+                    if _child_canister.wasm_version != child_wasm_data.wasm_version {
+                        _child_canister.wasm_version = child_wasm_data.wasm_version;
+                        Self::insert_canister(_child_canister.clone());
+                    }
+                    Self::set_child_module_hash(canister_principal, hash);
+                    return Ok(_child_canister);
                 }
 
                 let canister = Canister::from(_child_canister.principal);
                 // upgrade the child canister
                 let upgrade_result = canister
-                    .install_code(
-                        InstallCodeMode::Upgrade,
-                        data.child_wasm_data.bytes.clone(),
-                        (),
-                    )
+                    .install_code(InstallCodeMode::Upgrade, child_wasm_data.bytes.clone(), ())
                     .await;
                 match upgrade_result {
                     Err(err) => Err(api_error(
@@ -267,18 +592,15 @@ impl ScalableData {
                         "UPGRADE_FAILED",
                         &err.1.as_str(),
                         &Self::get_name(),
-                        "upgrade_scalable_canister",
+                        method_name,
                         inputs,
                     )),
                     Ok(_) => {
-                        // update child wasm version
-                        _child_canister.wasm_version = data.child_wasm_data.wasm_version;
-
-                        DATA.with(|v| {
-                            v.borrow_mut()
-                                .canisters
-                                .insert(canister_principal, _child_canister.clone())
-                        });
+                        // update child wasm version and module hash
+                        _child_canister.wasm_version = child_wasm_data.wasm_version;
+
+                        Self::insert_canister(_child_canister.clone());
+                        Self::set_child_module_hash(canister_principal, hash);
                         Ok(_child_canister)
                     }
                 }
@@ -288,16 +610,27 @@ impl ScalableData {
 
     // Method used to spawn an empty canister (not installed)
     async fn spawn_empty_canister() -> Result<Principal, ApiError> {
-        // Set canister settings
+        let config = Self::get_config();
+
+        // Set canister settings, this canister is always a controller of the child canisters
+        let mut controllers = vec![id()];
+        controllers.extend(
+            config
+                .child_canister_controllers
+                .into_iter()
+                .filter(|controller| controller != &id()),
+        );
+
         let canister_settings = CanisterSettings {
-            controllers: Some(vec![id()]),
+            controllers: Some(controllers),
             compute_allocation: None,
             memory_allocation: None,
             freezing_threshold: None,
         };
 
-        // Create canister with predefined amount of cycles
-        let new_canister = Canister::create(Some(canister_settings), 2_000_000_000_000).await;
+        // Create canister with the configured amount of cycles
+        let new_canister =
+            Canister::create(Some(canister_settings), config.child_canister_cycles).await;
         match new_canister {
             Err(err) => Err(api_error(
                 ApiErrorType::BadRequest,
@@ -318,11 +651,7 @@ impl ScalableData {
                 };
 
                 // Store child canister data on the parent
-                DATA.with(|v| {
-                    v.borrow_mut()
-                        .canisters
-                        .insert(new_canister_principal, canister_data)
-                });
+                Self::insert_canister(canister_data);
                 Ok(new_canister_principal)
             }
         }
@@ -336,8 +665,8 @@ impl ScalableData {
     ) -> Result<Principal, ApiError> {
         let inputs = Some(vec![format!("name - {}", &name.to_string())]);
 
-        let data = DATA.with(|v| v.borrow().clone());
-        if data.child_wasm_data.bytes.len() == 0 {
+        let child_wasm_data = Self::get_child_wasm_data();
+        if child_wasm_data.bytes.is_empty() {
             return Err(api_error(
                 ApiErrorType::BadRequest,
                 "NO_WASM_SPECIFIED",
@@ -348,11 +677,12 @@ impl ScalableData {
             ));
         }
 
+        let hash = Self::hash(&child_wasm_data.bytes);
         let install_canister = Canister::from(canister_principal)
             .install_code(
                 install_code_mode,
-                data.child_wasm_data.bytes,
-                (id(), name, data.canisters.iter().len()),
+                child_wasm_data.bytes,
+                (id(), name, CANISTERS.with(|c| c.borrow().len()) as usize),
             )
             .await;
 
@@ -368,56 +698,46 @@ impl ScalableData {
             Ok(_) => {
                 let new_child_details = ScalableCanisterDetails {
                     principal: canister_principal,
-                    wasm_version: data.child_wasm_data.wasm_version.clone(),
+                    wasm_version: child_wasm_data.wasm_version.clone(),
                     is_available: true,
                     canister_type: CanisterType::ScalableChild,
                     entry_range: (0, None),
                 };
 
-                DATA.with(|v| {
-                    v.borrow_mut()
-                        .canisters
-                        .insert(canister_principal, new_child_details)
-                });
-                Ok(canister_principal)
-            }
-        }
-    }
+                Self::insert_canister(new_child_details);
+                Self::set_child_module_hash(canister_principal, hash);
 
-    // Method used to upgrade all the child canister
-    pub async fn upgrade_children() {
-        let data = DATA.with(|data| data.borrow().clone());
-        for child in data.canisters {
-            if child.1.wasm_version != data.child_wasm_data.wasm_version {
-                match ScalableData::upgrade_child_canister(child.0.clone()).await {
-                    Ok(_details) => add_log(PostLog {
-                        log_type: LogType::Info,
-                        description: "Member child canister successfully upgraded".to_string(),
-                        source: "upgrade_children".to_string(),
-                        data: format!("{:?}", _details),
-                    }),
-                    Err(err) => add_log(PostLog {
+                // Push the current child config to the newly installed canister
+                if let Err(err) = Self::push_child_config(canister_principal).await {
+                    add_log(PostLog {
                         log_type: LogType::Error,
-                        description: "Member child canister not upgraded".to_string(),
-                        source: "upgrade_children".to_string(),
+                        description: "Child config not pushed".to_string(),
+                        source: "_install_child_canister".to_string(),
                         data: format!("{:?}", err),
-                    }),
-                };
+                    });
+                }
+                Ok(canister_principal)
             }
         }
     }
-    pub fn get_child_wasm_data(
-        old_store: &ScalableData,
-        version: u64,
-    ) -> Result<WasmDetails, String> {
+
+    // Method to get the child wasm details from the wasm that is included in this canister
+    pub fn get_embedded_child_wasm_data(version: u64) -> Result<WasmDetails, String> {
         let bytes = include_bytes!("../../../wasm/child.wasm.gz").to_vec();
 
         if bytes.is_empty() {
             return Err("No WASM found, skipping child WASM update".to_string());
         }
 
-        if old_store.child_wasm_data.bytes == bytes {
-            return Err("WASM is the same, skipping child WASM update".to_string());
+        // The child wasm is identified by the hash of the module, the version is only bumped when it differs
+        // the embedded wasm is compared with the wasm that was embedded last time as well, so an uploaded (newer) wasm
+        // is not replaced by an unchanged embedded wasm
+        let hash = Self::hash(&bytes);
+        let current_child_wasm_data = Self::get_child_wasm_data();
+        if Self::hash(&current_child_wasm_data.bytes) == hash
+            || Self::get_last_embedded_child_wasm_hash() == Some(hash)
+        {
+            return Err("WASM hash is the same, skipping child WASM update".to_string());
         }
 
         let details = WasmDetails {
@@ -426,101 +746,655 @@ impl ScalableData {
             wasm_type: CanisterType::ScalableChild,
             wasm_version: WasmVersion::Version(version),
             updated_at: time(),
-            created_at: old_store.child_wasm_data.created_at,
+            created_at: current_child_wasm_data.created_at,
         };
 
         Ok(details)
     }
 
-    fn get_name() -> String {
-        DATA.with(|v| v.borrow().name.clone())
+    // Method to get the hash of the child wasm that was embedded in this canister most recently
+    fn get_last_embedded_child_wasm_hash() -> Option<Vec<u8>> {
+        CHILD_WASM_HISTORY.with(|h| {
+            h.borrow()
+                .iter()
+                .filter(|(_, entry)| entry.source == ChildWasmSource::Embedded)
+                .last()
+                .map(|(_, entry)| entry.hash)
+        })
+    }
+
+    pub fn get_name() -> String {
+        DATA.with(|v| v.borrow().get().name.clone())
     }
 
     //
-    // MEMBERS
+    // CHILD WASM UPLOAD
     //
-    // Method used to get all the members from the child canisters paged
-    // requires composite queries to be released to mainnet
-    pub async fn get_joined_child_canister_data(
-        group_identifier: Principal,
-        limit: usize,
-        page: usize,
-    ) -> PagedResponse<JoinedMemberResponse> {
-        let canisters: Vec<Principal> = DATA.with(|data| {
-            data.borrow()
-                .canisters
-                .clone()
-                .into_iter()
-                .map(|c| c.1.principal.clone())
+    // Method to validate a child wasm upload, used by the SNS to render the proposal
+    pub fn validate_upload_child_wasm(
+        bytes: &[u8],
+        expected_hash: &[u8],
+    ) -> Result<String, String> {
+        if bytes.is_empty() {
+            return Err("The wasm is empty".to_string());
+        }
+
+        // A module is either a plain wasm module or a gzipped wasm module
+        if !bytes.starts_with(b"\0asm") && !bytes.starts_with(&[0x1f, 0x8b]) {
+            return Err("The bytes are not a (gzipped) wasm module".to_string());
+        }
+
+        let hash = Self::hash(bytes);
+        if hash != expected_hash {
+            return Err(format!(
+                "Hash mismatch, expected {} but got {}",
+                Self::to_hex(expected_hash),
+                Self::to_hex(&hash)
+            ));
+        }
+
+        let next_version = Self::get_next_child_wasm_version();
+        Ok(format!(
+            "Upload child wasm version {} with hash {} ({} bytes)",
+            next_version,
+            Self::to_hex(&hash),
+            bytes.len()
+        ))
+    }
+
+    // Method to store an uploaded child wasm as the new child wasm, the child canisters are upgraded afterwards
+    pub fn upload_child_wasm(
+        bytes: Vec<u8>,
+        expected_hash: Vec<u8>,
+    ) -> Result<WasmVersion, ApiError> {
+        if let Err(err) = Self::validate_upload_child_wasm(&bytes, &expected_hash) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_WASM",
+                err.as_str(),
+                &Self::get_name(),
+                "upload_child_wasm",
+                Some(vec![format!(
+                    "expected_hash - {}",
+                    Self::to_hex(&expected_hash)
+                )]),
+            ));
+        }
+
+        let current_child_wasm_data = Self::get_child_wasm_data();
+        if current_child_wasm_data.bytes == bytes {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "WASM_UNCHANGED",
+                "The uploaded wasm is the same as the current child wasm",
+                &Self::get_name(),
+                "upload_child_wasm",
+                None,
+            ));
+        }
+
+        let wasm_version = WasmVersion::Version(Self::get_next_child_wasm_version());
+        Self::set_child_wasm_data(
+            WasmDetails {
+                label: "child_member_canister".to_string(),
+                bytes,
+                wasm_type: CanisterType::ScalableChild,
+                wasm_version: wasm_version.clone(),
+                updated_at: time(),
+                created_at: current_child_wasm_data.created_at,
+            },
+            ChildWasmSource::Upload,
+        );
+
+        add_log(PostLog {
+            log_type: LogType::Info,
+            description: "Child wasm uploaded, canister children upgrading".to_string(),
+            source: "upload_child_wasm".to_string(),
+            data: format!("{:?}", wasm_version),
+        });
+
+        // Start a staged rollout to upgrade the child canisters
+        if let Err(err) = Rollout::start(None) {
+            add_log(PostLog {
+                log_type: LogType::Error,
+                description: "Child canister rollout not started".to_string(),
+                source: "upload_child_wasm".to_string(),
+                data: format!("{:?}", err),
+            });
+        }
+
+        Ok(wasm_version)
+    }
+
+    // Method to add a chunk to the child wasm that is being uploaded
+    // returns the total amount of bytes that are uploaded
+    pub fn upload_child_wasm_chunk(chunk: Vec<u8>) -> u64 {
+        CHILD_WASM_UPLOAD_CHUNKS.with(|u| {
+            let mut chunks = u.borrow_mut();
+            let offset = chunks
+                .last_key_value()
+                .map_or(0, |(offset, last_chunk)| offset + last_chunk.0.len() as u64);
+            let size = offset + chunk.len() as u64;
+            chunks.insert(offset, Stored(chunk));
+            size
+        })
+    }
+
+    // Method to remove the uploaded chunks
+    pub fn clear_child_wasm_upload() {
+        CHILD_WASM_UPLOAD_CHUNKS.with(|u| {
+            let mut chunks = u.borrow_mut();
+            let offsets: Vec<u64> = chunks.iter().map(|(offset, _)| offset).collect();
+            for offset in offsets {
+                chunks.remove(&offset);
+            }
+        });
+    }
+
+    // Method to concatenate the uploaded chunks in upload order
+    fn get_uploaded_child_wasm() -> Vec<u8> {
+        CHILD_WASM_UPLOAD_CHUNKS
+            .with(|u| u.borrow().iter().flat_map(|(_, chunk)| chunk.0).collect())
+    }
+
+    // Method to validate the uploaded chunks, used by the SNS to render the proposal
+    pub fn validate_finalize_child_wasm_upload(expected_hash: &[u8]) -> Result<String, String> {
+        let bytes = Self::get_uploaded_child_wasm();
+        Self::validate_upload_child_wasm(&bytes, expected_hash)
+    }
+
+    // Method to store the uploaded chunks as the new child wasm
+    pub fn finalize_child_wasm_upload(expected_hash: Vec<u8>) -> Result<WasmVersion, ApiError> {
+        let bytes = Self::get_uploaded_child_wasm();
+        let result = Self::upload_child_wasm(bytes, expected_hash);
+
+        // Only clear the uploaded chunks when the wasm is stored, so a mismatch can be inspected or cleared manually
+        if result.is_ok() {
+            Self::clear_child_wasm_upload();
+        }
+        result
+    }
+
+    // Method to get the version history of the child wasm, newest first
+    pub fn get_child_wasm_history() -> Vec<ChildWasmHistoryEntry> {
+        let mut history: Vec<ChildWasmHistoryEntry> =
+            CHILD_WASM_HISTORY.with(|h| h.borrow().iter().map(|(_, entry)| entry).collect());
+        history.reverse();
+        history
+    }
+
+    // Method to determine the next version of the child wasm
+    // the history is leading, as the current child wasm can be an older version after a rollback
+    pub fn get_next_child_wasm_version() -> u64 {
+        let latest_version =
+            CHILD_WASM_HISTORY.with(|h| h.borrow().last_key_value().map(|(v, _)| v));
+        match (latest_version, Self::get_child_wasm_data().wasm_version) {
+            (Some(_version), _) => _version + 1,
+            (None, WasmVersion::Version(_version)) => _version + 1,
+            _ => 0,
+        }
+    }
+
+    // Method to get the SHA-256 hash of the bytes
+    pub fn hash(bytes: &[u8]) -> Vec<u8> {
+        Sha256::digest(bytes).to_vec()
+    }
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    //
+    // MIGRATION
+    //
+    // Method to check if the stable memory contains data that is stored with `stable_save` by a previous version
+    // should be called before the stable structures are initialized, as the memory manager takes over the stable memory
+    pub fn has_legacy_stable_data() -> bool {
+        if stable64_size() == 0 {
+            return false;
+        }
+
+        // `stable_save` writes candid encoded data from the start of the stable memory
+        let mut magic = [0u8; 4];
+        stable64_read(0, &mut magic);
+        &magic == b"DIDL"
+    }
+
+    // Method to move the data stored with `stable_save` into the stable structures (one-time migration)
+    pub fn migrate_legacy_data(legacy_data: LegacyScalableData) {
+        let LegacyScalableData {
+            name,
+            canisters,
+            parent,
+            child_wasm_data,
+            config,
+            updated_at,
+            created_at,
+        } = legacy_data;
+
+        let _ = DATA.with(|d| {
+            d.borrow_mut().set(ParentData {
+                name,
+                parent,
+                updated_at,
+                created_at,
+            })
+        });
+
+        for (_, details) in canisters {
+            Self::insert_canister(details);
+        }
+
+        Self::set_child_wasm_data(child_wasm_data, ChildWasmSource::Embedded);
+
+        if let Some(config) = config {
+            let _ = CONFIG.with(|c| c.borrow_mut().set(config));
+        }
+    }
+
+    //
+    // MODULE HASHES
+    //
+    // Method to get the hash of the module that is installed on a child canister
+    // the stored hash is used when the canister status can not be retrieved
+    async fn get_installed_module_hash(canister_principal: Principal) -> Option<Vec<u8>> {
+        match canister_status(CanisterIdRecord {
+            canister_id: canister_principal,
+        })
+        .await
+        {
+            Ok((_status,)) => _status.module_hash,
+            Err(_) => Self::get_child_module_hash(&canister_principal),
+        }
+    }
+
+    pub fn get_child_module_hash(canister_principal: &Principal) -> Option<Vec<u8>> {
+        CHILD_MODULE_HASHES.with(|h| h.borrow().get(&canister_principal.to_string()).map(|h| h.0))
+    }
+
+    fn set_child_module_hash(canister_principal: Principal, hash: Vec<u8>) {
+        CHILD_MODULE_HASHES.with(|h| {
+            h.borrow_mut()
+                .insert(canister_principal.to_string(), Stored(hash))
+        });
+    }
+
+    // Method to get the hash of the module that is installed on every child canister
+    pub fn get_child_module_hashes() -> Vec<(Principal, Vec<u8>)> {
+        Self::get_canisters()
+            .into_iter()
+            .filter_map(|c| Self::get_child_module_hash(&c.principal).map(|h| (c.principal, h)))
+            .collect()
+    }
+
+    //
+    // ROLLBACK
+    //
+    // Method to add a child wasm to the archive, only the current and the previous `CHILD_WASM_ARCHIVE_SIZE` wasms are kept
+    fn archive_child_wasm(details: WasmDetails) {
+        let version = match details.wasm_version {
+            WasmVersion::Version(_version) => _version,
+            _ => return,
+        };
+
+        if details.bytes.is_empty() {
+            return;
+        }
+
+        CHILD_WASM_ARCHIVE.with(|a| {
+            let mut archive = a.borrow_mut();
+            if !archive.contains_key(&version) {
+                archive.insert(version, Stored(details));
+            }
+
+            while archive.len() > CHILD_WASM_ARCHIVE_SIZE + 1 {
+                match archive.first_key_value() {
+                    Some((_oldest, _)) => archive.remove(&_oldest),
+                    None => break,
+                };
+            }
+        });
+    }
+
+    // Method to get the versions of the child wasm that can be rolled back to, newest first
+    pub fn get_child_wasm_rollback_versions() -> Vec<WasmVersion> {
+        let mut versions: Vec<WasmVersion> = CHILD_WASM_ARCHIVE.with(|a| {
+            a.borrow()
+                .iter()
+                .map(|(version, _)| WasmVersion::Version(version))
                 .collect()
         });
+        versions.reverse();
+        versions
+    }
+
+    fn get_archived_child_wasm(
+        to_version: &WasmVersion,
+        method_name: &str,
+    ) -> Result<WasmDetails, ApiError> {
+        let archived = match to_version {
+            WasmVersion::Version(_version) => {
+                CHILD_WASM_ARCHIVE.with(|a| a.borrow().get(_version).map(|w| w.0))
+            }
+            _ => None,
+        };
+
+        archived.ok_or(api_error(
+            ApiErrorType::NotFound,
+            "WASM_VERSION_NOT_FOUND",
+            "The child wasm version is not available to roll back to",
+            &Self::get_name(),
+            method_name,
+            Some(vec![format!("to_version - {:?}", to_version)]),
+        ))
+    }
+
+    // Method to roll back a single child canister to an archived child wasm
+    pub async fn rollback_child_canister(
+        canister_principal: Principal,
+        to_version: WasmVersion,
+    ) -> Result<ScalableCanisterDetails, ApiError> {
+        let child_wasm_data =
+            Self::get_archived_child_wasm(&to_version, "rollback_child_canister")?;
+        Self::install_child_wasm(
+            canister_principal,
+            child_wasm_data,
+            "rollback_child_canister",
+        )
+        .await
+    }
+
+    // Method to roll back all the child canisters to an archived child wasm
+    // the archived wasm becomes the current child wasm, so new child canisters and rollouts use it as well
+    pub async fn rollback_children(
+        to_version: WasmVersion,
+    ) -> Result<Vec<Result<ScalableCanisterDetails, ApiError>>, ApiError> {
+        let mut child_wasm_data = Self::get_archived_child_wasm(&to_version, "rollback_children")?;
+        child_wasm_data.updated_at = time();
+        let _ = CHILD_WASM.with(|w| w.borrow_mut().set(Stored(child_wasm_data.clone())));
+
+        // Stop the active rollout from upgrading the child canisters to the newer wasm
+        let _ = Rollout::halt(format!("Rolled back to {:?}", to_version));
+
+        add_log(PostLog {
+            log_type: LogType::Info,
+            description: "Canister children rolling back".to_string(),
+            source: "rollback_children".to_string(),
+            data: format!("{:?}", to_version),
+        });
+
+        let mut results = vec![];
+        // Child canisters that already run the archived module are not touched
+        for canister in Self::get_canisters() {
+            let result = Self::install_child_wasm(
+                canister.principal,
+                child_wasm_data.clone(),
+                "rollback_children",
+            )
+            .await;
 
-        let mut joined: Vec<JoinedMemberResponse> = vec![];
-        for canister in canisters {
-            let mut canister_data =
-                Self::get_filtered_joined_child_data(canister, group_identifier).await;
-            joined.append(&mut canister_data);
+            if let Err(err) = &result {
+                add_log(PostLog {
+                    log_type: LogType::Error,
+                    description: "Child canister not rolled back".to_string(),
+                    source: "rollback_children".to_string(),
+                    data: format!("{:?}", err),
+                });
+            }
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    //
+    // CONFIG
+    //
+    // Method to get the runtime configuration
+    pub fn get_config() -> ParentConfig {
+        CONFIG.with(|c| c.borrow().get().clone())
+    }
+
+    // Method to validate and store a new runtime configuration
+    // the child config is pushed to all the child canisters afterwards
+    pub fn update_config(config: ParentConfig) -> Result<ParentConfig, ApiError> {
+        let inputs = Some(vec![format!("config - {:?}", &config)]);
+
+        if config.child_canister_cycles < 500_000_000_000 {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_CYCLES",
+                "A child canister needs to be created with at least 0.5T cycles",
+                &Self::get_name(),
+                "update_config",
+                inputs,
+            ));
+        }
+
+        if config.max_bytes_per_chunk == 0
+            || config.max_bytes_per_chunk > 2_000_000
+            || config.child_config.max_bytes_per_chunk == 0
+            || config.child_config.max_bytes_per_chunk > 2_000_000
+        {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_CHUNK_SIZE",
+                "The max bytes per chunk should be between 1 and 2_000_000 bytes",
+                &Self::get_name(),
+                "update_config",
+                inputs,
+            ));
+        }
+
+        if config.child_config.max_entries == Some(0) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_MAX_ENTRIES",
+                "The max entries of a child canister should be greater than 0",
+                &Self::get_name(),
+                "update_config",
+                inputs,
+            ));
         }
 
-        get_paged_data(joined, limit, page)
+        if [
+            &config.child_config.principal_rate_limit,
+            &config.child_config.group_rate_limit,
+        ]
+        .iter()
+        .any(|rate_limit| {
+            rate_limit.as_ref().is_some_and(|_rate_limit| {
+                _rate_limit.capacity == 0 || _rate_limit.refill_per_minute == 0
+            })
+        }) {
+            return Err(api_error(
+                ApiErrorType::BadRequest,
+                "INVALID_RATE_LIMIT",
+                "The capacity and refill per minute of a rate limit should be greater than 0",
+                &Self::get_name(),
+                "update_config",
+                inputs,
+            ));
+        }
+
+        let _ = CONFIG.with(|c| c.borrow_mut().set(config.clone()));
+
+        // Use a timer to push the child config to all the child canisters
+        set_timer(Duration::from_secs(0), || {
+            ic_cdk::spawn(Self::push_child_config_to_children());
+        });
+
+        Ok(config)
     }
 
-    // Method to get the data from a single child canister in chunks
+    // Method to push the child config to a single child canister (inter-canister call)
+    async fn push_child_config(canister_principal: Principal) -> Result<(), ApiError> {
+        let child_config = Self::get_config().child_config;
+        let result: Result<(Result<(), ApiError>,), _> =
+            call::call(canister_principal, "set_config", (child_config,)).await;
+
+        match result {
+            Err(err) => Err(api_error(
+                ApiErrorType::BadRequest,
+                "INTER_CANISTER_CALL_FAILED",
+                err.1.as_str(),
+                &Self::get_name(),
+                "push_child_config",
+                Some(vec![format!("canister_principal - {}", canister_principal)]),
+            )),
+            Ok((_result,)) => _result,
+        }
+    }
+
+    // Method used to push the child config to all the child canisters
+    pub async fn push_child_config_to_children() {
+        for canister_principal in Self::get_canisters().into_iter().map(|c| c.principal) {
+            match Self::push_child_config(canister_principal).await {
+                Ok(_) => add_log(PostLog {
+                    log_type: LogType::Info,
+                    description: "Child config pushed".to_string(),
+                    source: "push_child_config_to_children".to_string(),
+                    data: canister_principal.to_string(),
+                }),
+                Err(err) => add_log(PostLog {
+                    log_type: LogType::Error,
+                    description: "Child config not pushed".to_string(),
+                    source: "push_child_config_to_children".to_string(),
+                    data: format!("{:?}", err),
+                }),
+            }
+        }
+    }
+
+    //
+    // FAN-OUT
+    //
+    // Method to call every child canister with bounded parallelism
+    // the results are merged in the order of the child canisters, so the merged data is deterministic
+    pub(crate) async fn fan_out_to_children<T, F, Fut>(call: F) -> (Vec<T>, Vec<ChildCallFailure>)
+    where
+        F: Fn(Principal) -> Fut,
+        Fut: Future<Output = Result<Vec<T>, String>>,
+    {
+        let canisters: Vec<Principal> = Self::get_canisters()
+            .into_iter()
+            .map(|c| c.principal)
+            .collect();
+
+        let mut data: Vec<T> = vec![];
+        let mut failures: Vec<ChildCallFailure> = vec![];
+
+        for batch in canisters.chunks(MAX_PARALLEL_CHILD_CALLS) {
+            let results = join_all(batch.iter().map(|canister| call(*canister))).await;
+
+            for (canister, result) in batch.iter().zip(results) {
+                match result {
+                    Ok(mut _data) => data.append(&mut _data),
+                    Err(reason) => failures.push(ChildCallFailure {
+                        canister: *canister,
+                        reason,
+                    }),
+                }
+            }
+        }
+
+        (data, failures)
+    }
+
+    //
+    // CHUNKED CHILD DATA
+    //
+    // Method to get the complete data of a group from a single child canister through the versioned chunk protocol
+    // the first chunk determines the snapshot, when the data changes between the chunk calls the transfer is retried
     // requires composite queries to be released to mainnet
-    async fn get_filtered_joined_child_data(
+    async fn get_chunked_child_data<T: DeserializeOwned>(
         canister_principal: Principal,
+        method_name: &str,
         group_identifier: Principal,
-    ) -> Vec<JoinedMemberResponse> {
-        // Do initial fetch of the first chunk and determine the number of chunks
-        let (mut bytes, (_, last)) =
-            Self::get_chunked_joined_child_data(canister_principal, group_identifier, 0, None)
-                .await;
+    ) -> Result<Vec<T>, String> {
+        let max_bytes_per_chunk = Self::get_config().max_bytes_per_chunk;
+        let request = |chunk: u64| ChunkRequest {
+            version: CHUNK_PROTOCOL_VERSION,
+            group_identifier,
+            chunk,
+            max_bytes_per_chunk,
+            // The data is compressed by the child canister and decompressed when the chunks are assembled
+            accepted_codecs: Some(vec![ChunkCodec::Gzip, ChunkCodec::Identity]),
+        };
 
-        // If there are more chunks, fetch them
-        if last > 1 {
-            for i in 1..last + 1 {
-                let (mut _bytes, _) = Self::get_chunked_joined_child_data(
-                    canister_principal,
-                    group_identifier,
-                    i,
-                    None,
-                )
-                .await;
-                // Append the bytes to the first chunk
-                bytes.append(&mut _bytes);
+        for _ in 0..MAX_CHUNK_TRANSFER_ATTEMPTS {
+            // Fetch the first chunk to determine the snapshot and the number of chunks
+            let first = Self::get_data_chunk(canister_principal, method_name, request(0)).await?;
+
+            let mut chunks = vec![first.clone()];
+            let mut snapshot_changed = false;
+            for chunk in 1..first.chunk_count {
+                let data_chunk =
+                    Self::get_data_chunk(canister_principal, method_name, request(chunk)).await?;
+
+                match data_chunk.verify(&first) {
+                    Ok(_) => chunks.push(data_chunk),
+                    Err(ChunkError::SnapshotChanged) => {
+                        snapshot_changed = true;
+                        break;
+                    }
+                    Err(err) => return Err(format!("{:?}", err)),
+                }
             }
-        }
 
-        // Deserialize the bytes to the correct data type
-        match deserialize::<Vec<JoinedMemberResponse>>(bytes.clone()) {
-            Ok(_res) => _res,
-            Err(_err) => {
-                ic_cdk::println!("Error: {}", _err);
-                vec![]
+            if snapshot_changed {
+                continue;
             }
+
+            return match assemble_chunks(chunks) {
+                Ok(_bytes) => deserialize::<Vec<T>>(_bytes)
+                    .map_err(|err| format!("Deserialization failed: {}", err)),
+                Err(ChunkError::SnapshotChanged) => continue,
+                Err(err) => Err(format!("{:?}", err)),
+            };
         }
+
+        Err(format!("{:?}", ChunkError::SnapshotChanged))
     }
 
-    // Inter canister call to fetch the chunked data from the child canister
-    async fn get_chunked_joined_child_data(
+    // Inter canister call to fetch a single chunk from the child canister
+    async fn get_data_chunk(
         canister_principal: Principal,
+        method_name: &str,
+        request: ChunkRequest,
+    ) -> Result<DataChunk, String> {
+        let result: Result<(Result<DataChunk, ChunkError>,), _> =
+            call::call(canister_principal, method_name, (request,)).await;
+
+        match result {
+            Ok((Ok(_chunk),)) => Ok(_chunk),
+            Ok((Err(err),)) => Err(format!("{:?}", err)),
+            Err((code, message)) => Err(format!("{:?}: {}", code, message)),
+        }
+    }
+
+    //
+    // MEMBERS
+    //
+    // Method used to get all the members from the child canisters paged
+    // requires composite queries to be released to mainnet
+    pub async fn get_joined_child_canister_data(
         group_identifier: Principal,
-        chunk: usize,
-        max_bytes_per_chunk: Option<usize>,
-    ) -> (Vec<u8>, (usize, usize)) {
-        // If the max bytes per chunk is not provided, use the default of 2_000_000 (2mb)
-        let _max_bytes_per_chunk = max_bytes_per_chunk.unwrap_or(2_000_000);
-        let result: Result<(Vec<u8>, (usize, usize)), _> = call::call(
-            canister_principal,
-            "get_chunked_join_data",
-            (group_identifier, chunk, _max_bytes_per_chunk),
-        )
+        limit: usize,
+        page: usize,
+    ) -> ChildrenPagedResponse<JoinedMemberResponse> {
+        let (joined, failed_children) = Self::fan_out_to_children(|canister| {
+            Self::get_chunked_child_data::<JoinedMemberResponse>(
+                canister,
+                "get_join_data_chunk",
+                group_identifier,
+            )
+        })
         .await;
 
-        // return the bytes and the chunk info, if there is an error, return an empty vec and chunk info
-        match result {
-            Ok(_res) => _res,
-            _ => (vec![], (0, 0)),
+        ChildrenPagedResponse {
+            data: get_paged_data(joined, limit, page),
+            failed_children,
         }
     }
 
@@ -533,82 +1407,207 @@ impl ScalableData {
         group_identifier: Principal,
         limit: usize,
         page: usize,
-    ) -> PagedResponse<InviteMemberResponse> {
-        let canisters: Vec<Principal> = DATA.with(|data| {
-            data.borrow()
-                .canisters
-                .clone()
-                .into_iter()
-                .map(|c| c.1.principal.clone())
-                .collect()
-        });
+    ) -> ChildrenPagedResponse<InviteMemberResponse> {
+        let (invites, failed_children) = Self::fan_out_to_children(|canister| {
+            Self::get_chunked_child_data::<InviteMemberResponse>(
+                canister,
+                "get_invite_data_chunk",
+                group_identifier,
+            )
+        })
+        .await;
 
-        let mut joined: Vec<InviteMemberResponse> = vec![];
-        for canister in canisters {
-            let mut canister_data =
-                Self::get_filtered_invites_child_data(canister, group_identifier).await;
-            joined.append(&mut canister_data);
+        ChildrenPagedResponse {
+            data: get_paged_data(invites, limit, page),
+            failed_children,
         }
-
-        get_paged_data(joined, limit, page)
     }
 
-    // Method to get the data from a single child canister in chunks
+    //
+    // SEARCH
+    //
+    // Method to search the members of a group on all child canisters, the merged results are ranked by match quality
     // requires composite queries to be released to mainnet
-    async fn get_filtered_invites_child_data(
-        canister_principal: Principal,
+    pub async fn search_group_members(
         group_identifier: Principal,
-    ) -> Vec<InviteMemberResponse> {
-        // Do initial fetch of the first chunk and determine the number of chunks
-        let (mut bytes, (_, last)) =
-            Self::get_chunked_invites_child_data(canister_principal, group_identifier, 0, None)
-                .await;
+        query: String,
+        limit: usize,
+    ) -> MemberSearchResponse {
+        let limit = limit.min(MAX_SEARCH_RESULTS);
+        let (mut results, failed_children) = Self::fan_out_to_children(|canister| {
+            Self::search_child_group_members(canister, group_identifier, query.clone(), limit)
+        })
+        .await;
 
-        // If there are more chunks, fetch them
-        if last > 1 {
-            for i in 1..last + 1 {
-                let (mut _bytes, _) = Self::get_chunked_invites_child_data(
-                    canister_principal,
-                    group_identifier,
-                    i,
-                    None,
-                )
-                .await;
-                // Append the bytes to the first chunk
-                bytes.append(&mut _bytes);
-            }
-        }
+        results.sort_by(|a, b| {
+            b.score.cmp(&a.score).then_with(|| {
+                a.member
+                    .principal
+                    .to_string()
+                    .cmp(&b.member.principal.to_string())
+            })
+        });
+        results.truncate(limit);
 
-        // Deserialize the bytes to the correct data type
-        match deserialize::<Vec<InviteMemberResponse>>(bytes.clone()) {
-            Ok(_res) => _res,
-            Err(_err) => {
-                ic_cdk::println!("Error: {}", _err);
-                vec![]
-            }
+        MemberSearchResponse {
+            data: results,
+            failed_children,
         }
     }
 
-    // Inter canister call to fetch the chunked data from the child canister
-    async fn get_chunked_invites_child_data(
+    // Inter canister call to search the members of a group on a single child canister
+    async fn search_child_group_members(
         canister_principal: Principal,
         group_identifier: Principal,
-        chunk: usize,
-        max_bytes_per_chunk: Option<usize>,
-    ) -> (Vec<u8>, (usize, usize)) {
-        // If the max bytes per chunk is not provided, use the default of 2_000_000 (2mb)
-        let _max_bytes_per_chunk = max_bytes_per_chunk.unwrap_or(2_000_000);
-        let result: Result<(Vec<u8>, (usize, usize)), _> = call::call(
+        query: String,
+        limit: usize,
+    ) -> Result<Vec<MemberSearchResult>, String> {
+        let result: Result<(Vec<MemberSearchResult>,), _> = call::call(
             canister_principal,
-            "get_chunked_invite_data",
-            (group_identifier, chunk, _max_bytes_per_chunk),
+            "search_group_members",
+            (group_identifier, query, limit),
         )
         .await;
 
-        // return the bytes and the chunk info, if there is an error, return an empty vec and chunk info
-        match result {
-            Ok(_res) => _res,
-            _ => (vec![], (0, 0)),
+        result
+            .map(|(_results,)| _results)
+            .map_err(|(code, message)| format!("{:?}: {}", code, message))
+    }
+
+    //
+    // MEMBERS ACROSS CHILD CANISTERS
+    //
+    // Method to find the member entries of user principals, wherever they are stored
+    // returns the child canister, the member identifier and the member entry per found principal
+    // requires composite queries to be released to mainnet
+    async fn find_members(
+        principals: Vec<Principal>,
+    ) -> (Vec<(Principal, Principal, Member)>, Vec<ChildCallFailure>) {
+        Self::fan_out_to_children(|canister| Self::get_child_members(canister, principals.clone()))
+            .await
+    }
+
+    // Inter canister call to get the member entries of user principals from a single child canister
+    async fn get_child_members(
+        canister_principal: Principal,
+        principals: Vec<Principal>,
+    ) -> Result<Vec<(Principal, Principal, Member)>, String> {
+        let result: Result<(Vec<(Principal, Member)>,), _> = call::call(
+            canister_principal,
+            "get_members_by_principals",
+            (principals,),
+        )
+        .await;
+
+        result
+            .map(|(_members,)| {
+                _members
+                    .into_iter()
+                    .map(|(_identifier, _member)| (canister_principal, _identifier, _member))
+                    .collect()
+            })
+            .map_err(|(code, message)| format!("{:?}: {}", code, message))
+    }
+
+    // Method to get the groups two members both joined, with the roles of each member
+    // requires composite queries to be released to mainnet
+    pub async fn get_mutual_groups(first: Principal, second: Principal) -> MutualGroupsResponse {
+        let (members, failed_children) = Self::find_members(vec![first, second]).await;
+
+        let find = |principal: Principal| {
+            members
+                .iter()
+                .find(|(_, _, _member)| _member.principal == principal)
+                .map(|(_, _, _member)| _member)
+        };
+
+        let mut data: Vec<MutualGroup> = match (find(first), find(second)) {
+            (Some(_first), Some(_second)) => _first
+                .joined
+                .iter()
+                .filter_map(|(_group_identifier, _first_join)| {
+                    _second
+                        .joined
+                        .get(_group_identifier)
+                        .map(|_second_join| MutualGroup {
+                            group_identifier: *_group_identifier,
+                            first_roles: _first_join.roles.clone(),
+                            second_roles: _second_join.roles.clone(),
+                        })
+                })
+                .collect(),
+            _ => vec![],
+        };
+        data.sort_by_key(|group| group.group_identifier.to_string());
+
+        MutualGroupsResponse {
+            data,
+            failed_children,
+        }
+    }
+
+    // Method to get the groups a user joined, with the roles and timestamps, wherever the member entry is stored
+    // requires composite queries to be released to mainnet
+    pub async fn get_groups_for_principal(principal: Principal) -> PrincipalGroupsResponse<Join> {
+        Self::get_principal_groups(principal, |member| {
+            member
+                .joined
+                .into_iter()
+                .map(|(_group_identifier, _join)| (_group_identifier, _join.created_at, _join))
+                .collect()
+        })
+        .await
+    }
+
+    // Method to get the pending invites of a user, with the invite type, wherever the member entry is stored
+    // requires composite queries to be released to mainnet
+    pub async fn get_invites_for_principal(
+        principal: Principal,
+    ) -> PrincipalGroupsResponse<Invite> {
+        Self::get_principal_groups(principal, |member| {
+            member
+                .invites
+                .into_iter()
+                .map(|(_group_identifier, _invite)| {
+                    (_group_identifier, _invite.created_at, _invite)
+                })
+                .collect()
+        })
+        .await
+    }
+
+    // Method to resolve the member entry of a user and map it to (group identifier, created at, data) entries
+    async fn get_principal_groups<T>(
+        principal: Principal,
+        map: impl FnOnce(Member) -> Vec<(Principal, u64, T)>,
+    ) -> PrincipalGroupsResponse<T> {
+        let (members, failed_children) = Self::find_members(vec![principal]).await;
+
+        match members.into_iter().next() {
+            None => PrincipalGroupsResponse {
+                canister: None,
+                member_identifier: None,
+                data: vec![],
+                failed_children,
+            },
+            Some((_canister, _member_identifier, _member)) => {
+                let mut entries = map(_member);
+                // Newest first, ties are ordered by group identifier
+                entries.sort_by(|a, b| {
+                    b.1.cmp(&a.1)
+                        .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+                });
+
+                PrincipalGroupsResponse {
+                    canister: Some(_canister),
+                    member_identifier: Some(_member_identifier),
+                    data: entries
+                        .into_iter()
+                        .map(|(_group_identifier, _, _data)| (_group_identifier, _data))
+                        .collect(),
+                    failed_children,
+                }
+            }
         }
     }
 }
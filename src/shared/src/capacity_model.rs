@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct GroupCapacity {
+    pub group_identifier: Principal,
+    // The maximum amount of members of the group
+    pub capacity: u64,
+    // The amount of seats that are taken, counted across all child canisters
+    pub members: u64,
+    // The joins past the capacity, in order of arrival
+    pub waitlist: Vec<WaitlistEntry>,
+    pub updated_at: u64,
+}
+
+impl Storable for GroupCapacity {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct WaitlistEntry {
+    pub principal: Principal,
+    // The child canister that received the join, the member is added there when promoted
+    pub canister: Principal,
+    // The role the member gets when promoted
+    pub role: String,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SeatClaim {
+    // The join can be added, also returned for groups without a capacity
+    Granted,
+    // The group is at capacity and the join is added to the waitlist, the position is 1-based
+    Waitlisted { position: u64 },
+}
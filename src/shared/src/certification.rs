@@ -0,0 +1,220 @@
+use std::cmp::Ordering;
+
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// Label of the membership tree in the certified data of a child canister
+pub static MEMBERSHIP_LABEL: &[u8] = b"membership";
+
+pub type Hash = [u8; 32];
+
+// Response of a certified query, the witness proves the data against the certified data of the canister
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct CertifiedResponse<T> {
+    pub data: T,
+    // The certificate returned by `data_certificate`, empty when the query is called as update
+    pub certificate: Vec<u8>,
+    // CBOR encoded hash tree with the witness for the data
+    pub witness: Vec<u8>,
+}
+
+//
+// KEYS AND VALUES
+// used by the child canister to build the certified tree and by clients to verify the responses
+//
+// Key of the roles of a member within a group
+pub fn member_key(group_identifier: &Principal, principal: &Principal) -> Vec<u8> {
+    format!("member/{}/{}", group_identifier, principal).into_bytes()
+}
+
+// Key of the member count of a group, absent when the group has no members
+pub fn count_key(group_identifier: &Principal) -> Vec<u8> {
+    format!("count/{}", group_identifier).into_bytes()
+}
+
+// Hash of the roles of a member within a group
+pub fn roles_hash(roles: &[String]) -> Hash {
+    let mut hasher = Sha256::new();
+    for role in roles {
+        hasher.update((role.len() as u64).to_be_bytes());
+        hasher.update(role.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+// Hash of the member count of a group
+pub fn count_hash(count: u64) -> Hash {
+    Sha256::digest(count.to_be_bytes()).into()
+}
+
+//
+// VERIFICATION
+// the decoding of the certificates and the BLS signature check are behind the `verifier` feature
+// so the canisters that only build the certified tree do not pull in these dependencies
+//
+#[cfg(feature = "verifier")]
+mod verifier;
+#[cfg(feature = "verifier")]
+pub use verifier::{verify_certified_response, MAX_CERTIFICATE_AGE_NANOS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashTree {
+    Empty,
+    Fork(Box<HashTree>, Box<HashTree>),
+    Labeled(Vec<u8>, Box<HashTree>),
+    Leaf(Vec<u8>),
+    Pruned(Hash),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupResult<'a> {
+    Found(&'a [u8]),
+    // The path is provably not in the tree
+    Absent,
+    // The path is pruned from the tree, nothing can be said about it
+    Unknown,
+}
+
+// Result of looking up a label within the labeled children of a node, the labels are ordered
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum LookupLabelResult<'a> {
+    Found(&'a HashTree),
+    Absent,
+    Unknown,
+    // The label is smaller than all labels of the node
+    Less,
+    // The label is greater than all labels of the node
+    Greater,
+}
+
+impl HashTree {
+    // Method to compute the root hash of the tree
+    pub fn reconstruct(&self) -> Hash {
+        match self {
+            HashTree::Empty => hash_with_domain(b"ic-hashtree-empty", &[]),
+            HashTree::Fork(left, right) => hash_with_domain(
+                b"ic-hashtree-fork",
+                &[&left.reconstruct(), &right.reconstruct()],
+            ),
+            HashTree::Labeled(label, subtree) => {
+                hash_with_domain(b"ic-hashtree-labeled", &[label, &subtree.reconstruct()])
+            }
+            HashTree::Leaf(leaf) => hash_with_domain(b"ic-hashtree-leaf", &[leaf]),
+            HashTree::Pruned(hash) => *hash,
+        }
+    }
+
+    // Method to look up the value at a path of labels, following `lookup_path` of the IC interface specification
+    // the labels of a node are ordered, so a label between two known labels is provably absent even if other
+    // parts of the tree are pruned
+    pub fn lookup(&self, path: &[&[u8]]) -> LookupResult<'_> {
+        let (label, rest) = match path.split_first() {
+            None => {
+                return match self {
+                    HashTree::Leaf(_leaf) => LookupResult::Found(_leaf),
+                    HashTree::Pruned(_) => LookupResult::Unknown,
+                    _ => LookupResult::Absent,
+                }
+            }
+            Some(_split) => _split,
+        };
+
+        match self.find_label(label) {
+            LookupLabelResult::Found(_subtree) => _subtree.lookup(rest),
+            LookupLabelResult::Unknown => LookupResult::Unknown,
+            LookupLabelResult::Absent | LookupLabelResult::Less | LookupLabelResult::Greater => {
+                LookupResult::Absent
+            }
+        }
+    }
+
+    fn find_label(&self, label: &[u8]) -> LookupLabelResult<'_> {
+        match self {
+            HashTree::Labeled(_label, _subtree) => match label.cmp(_label.as_slice()) {
+                Ordering::Equal => LookupLabelResult::Found(_subtree),
+                Ordering::Less => LookupLabelResult::Less,
+                Ordering::Greater => LookupLabelResult::Greater,
+            },
+            HashTree::Fork(left, right) => match left.find_label(label) {
+                // The label is between the labels of the left and the right subtree
+                LookupLabelResult::Greater => match right.find_label(label) {
+                    LookupLabelResult::Less => LookupLabelResult::Absent,
+                    _result => _result,
+                },
+                LookupLabelResult::Unknown => match right.find_label(label) {
+                    LookupLabelResult::Less => LookupLabelResult::Unknown,
+                    _result => _result,
+                },
+                _result => _result,
+            },
+            HashTree::Pruned(_) => LookupLabelResult::Unknown,
+            HashTree::Empty | HashTree::Leaf(_) => LookupLabelResult::Absent,
+        }
+    }
+}
+
+fn domain_separator(domain: &[u8]) -> Vec<u8> {
+    let mut separator = vec![domain.len() as u8];
+    separator.extend_from_slice(domain);
+    separator
+}
+
+fn hash_with_domain(domain: &[u8], parts: &[&[u8]]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(domain_separator(domain));
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labeled(label: &[u8], subtree: HashTree) -> HashTree {
+        HashTree::Labeled(label.to_vec(), Box::new(subtree))
+    }
+
+    fn fork(left: HashTree, right: HashTree) -> HashTree {
+        HashTree::Fork(Box::new(left), Box::new(right))
+    }
+
+    fn leaf(value: &[u8]) -> HashTree {
+        HashTree::Leaf(value.to_vec())
+    }
+
+    // Witness as produced by `RbTree` revealing the keys `b` and `d`, the rest of the tree is pruned
+    fn witness() -> HashTree {
+        fork(
+            fork(HashTree::Pruned([0; 32]), labeled(b"b", leaf(b"1"))),
+            fork(labeled(b"d", leaf(b"2")), HashTree::Pruned([1; 32])),
+        )
+    }
+
+    #[test]
+    fn lookup_present() {
+        assert_eq!(witness().lookup(&[b"b"]), LookupResult::Found(b"1"));
+        assert_eq!(witness().lookup(&[b"d"]), LookupResult::Found(b"2"));
+    }
+
+    #[test]
+    fn lookup_absent() {
+        // `c` is between the revealed labels `b` and `d`
+        assert_eq!(witness().lookup(&[b"c"]), LookupResult::Absent);
+        assert_eq!(leaf(b"1").lookup(&[b"a"]), LookupResult::Absent);
+        assert_eq!(HashTree::Empty.lookup(&[b"a"]), LookupResult::Absent);
+    }
+
+    #[test]
+    fn lookup_pruned() {
+        // `a` and `e` can be in the pruned parts of the tree
+        assert_eq!(witness().lookup(&[b"a"]), LookupResult::Unknown);
+        assert_eq!(witness().lookup(&[b"e"]), LookupResult::Unknown);
+        assert_eq!(
+            labeled(b"b", HashTree::Pruned([0; 32])).lookup(&[b"b"]),
+            LookupResult::Unknown
+        );
+    }
+}
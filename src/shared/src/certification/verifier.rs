@@ -0,0 +1,285 @@
+use std::convert::TryInto;
+
+use candid::Principal;
+use serde_cbor::Value;
+
+use super::{domain_separator, CertifiedResponse, Hash, HashTree, LookupResult, MEMBERSHIP_LABEL};
+
+// DER prefix of the BLS public key of the IC (root or subnet)
+static BLS_DER_PREFIX_LENGTH: usize = 37;
+
+// The maximum age of a certificate in nanoseconds, older certificates are rejected so old responses can not be replayed
+pub static MAX_CERTIFICATE_AGE_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+impl HashTree {
+    // Method to decode a CBOR encoded hash tree, as returned in the certificate and witness
+    pub fn from_cbor(bytes: &[u8]) -> Result<HashTree, String> {
+        let value: Value = serde_cbor::from_slice(bytes).map_err(|err| err.to_string())?;
+        Self::from_value(strip_self_describe_tag(value))
+    }
+
+    fn from_value(value: Value) -> Result<HashTree, String> {
+        let mut items = match value {
+            Value::Array(_items) => _items.into_iter(),
+            _ => return Err("Hash tree node should be an array".to_string()),
+        };
+
+        match (items.next(), items.next(), items.next()) {
+            (Some(Value::Integer(0)), None, None) => Ok(HashTree::Empty),
+            (Some(Value::Integer(1)), Some(left), Some(right)) => Ok(HashTree::Fork(
+                Box::new(Self::from_value(left)?),
+                Box::new(Self::from_value(right)?),
+            )),
+            (Some(Value::Integer(2)), Some(Value::Bytes(label)), Some(subtree)) => Ok(
+                HashTree::Labeled(label, Box::new(Self::from_value(subtree)?)),
+            ),
+            (Some(Value::Integer(3)), Some(Value::Bytes(leaf)), None) => Ok(HashTree::Leaf(leaf)),
+            (Some(Value::Integer(4)), Some(Value::Bytes(hash)), None) => {
+                let hash: Hash = hash
+                    .try_into()
+                    .map_err(|_| "Pruned hash should be 32 bytes".to_string())?;
+                Ok(HashTree::Pruned(hash))
+            }
+            _ => Err("Invalid hash tree node".to_string()),
+        }
+    }
+}
+
+// Method to verify a certified response of a child canister (client-side)
+// `root_key` is the DER encoded public key of the IC, `expected_value` is `None` when the data claims the key is absent
+// `now` is the current time in nanoseconds since the epoch, used to reject certificates older than `MAX_CERTIFICATE_AGE_NANOS`
+pub fn verify_certified_response<T>(
+    response: &CertifiedResponse<T>,
+    canister_id: &Principal,
+    root_key: &[u8],
+    key: &[u8],
+    expected_value: Option<Hash>,
+    now: u64,
+) -> Result<(), String> {
+    if response.certificate.is_empty() {
+        return Err("The response has no certificate, call the method as query".to_string());
+    }
+
+    let certified_data = verify_certificate(&response.certificate, canister_id, root_key, now)?;
+
+    let witness = HashTree::from_cbor(&response.witness)?;
+    if witness.reconstruct().to_vec() != certified_data {
+        return Err("The witness does not match the certified data".to_string());
+    }
+
+    match (witness.lookup(&[MEMBERSHIP_LABEL, key]), expected_value) {
+        (LookupResult::Found(_value), Some(_expected)) if _value == _expected => Ok(()),
+        (LookupResult::Absent, None) => Ok(()),
+        (LookupResult::Unknown, _) => Err("The witness does not cover the key".to_string()),
+        _ => Err("The data does not match the witness".to_string()),
+    }
+}
+
+// Method to verify the certificate signature and return the certified data of the canister
+fn verify_certificate(
+    certificate: &[u8],
+    canister_id: &Principal,
+    root_key: &[u8],
+    now: u64,
+) -> Result<Vec<u8>, String> {
+    let (tree, signature, delegation) = decode_certificate(certificate)?;
+    check_certificate_time(&tree, now)?;
+
+    // A subnet certificate is signed with the subnet key, which is certified by the root key through the delegation
+    let public_key = match delegation {
+        None => root_key.to_vec(),
+        Some((_subnet_id, _delegation_certificate)) => {
+            let (delegation_tree, delegation_signature, nested_delegation) =
+                decode_certificate(&_delegation_certificate)?;
+            if nested_delegation.is_some() {
+                return Err("Nested delegations are not allowed".to_string());
+            }
+            verify_signature(&delegation_tree, &delegation_signature, root_key)?;
+            check_canister_ranges(&delegation_tree, &_subnet_id, canister_id)?;
+
+            match delegation_tree.lookup(&[&b"subnet"[..], &_subnet_id, &b"public_key"[..]]) {
+                LookupResult::Found(_public_key) => _public_key.to_vec(),
+                _ => return Err("The delegation has no subnet public key".to_string()),
+            }
+        }
+    };
+    verify_signature(&tree, &signature, &public_key)?;
+
+    match tree.lookup(&[
+        &b"canister"[..],
+        canister_id.as_slice(),
+        &b"certified_data"[..],
+    ]) {
+        LookupResult::Found(_certified_data) => Ok(_certified_data.to_vec()),
+        _ => Err("The certificate has no certified data for the canister".to_string()),
+    }
+}
+
+// Method to check if the certificate is not older than `MAX_CERTIFICATE_AGE_NANOS`
+fn check_certificate_time(tree: &HashTree, now: u64) -> Result<(), String> {
+    let time = match tree.lookup(&[&b"time"[..]]) {
+        LookupResult::Found(_time) => decode_leb128(_time)?,
+        _ => return Err("The certificate has no time".to_string()),
+    };
+
+    match now.saturating_sub(time) > MAX_CERTIFICATE_AGE_NANOS {
+        true => Err("The certificate is too old".to_string()),
+        false => Ok(()),
+    }
+}
+
+// Method to decode an unsigned LEB128 encoded number, as used for the time in the certificate
+fn decode_leb128(bytes: &[u8]) -> Result<u64, String> {
+    let mut value: u64 = 0;
+    for (index, byte) in bytes.iter().enumerate() {
+        if index >= 10 {
+            return Err("The LEB128 number is too large".to_string());
+        }
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("The LEB128 number is incomplete".to_string())
+}
+
+type DecodedCertificate = (HashTree, Vec<u8>, Option<(Vec<u8>, Vec<u8>)>);
+
+fn decode_certificate(certificate: &[u8]) -> Result<DecodedCertificate, String> {
+    let value: Value = serde_cbor::from_slice(certificate).map_err(|err| err.to_string())?;
+    let map = match strip_self_describe_tag(value) {
+        Value::Map(_map) => _map,
+        _ => return Err("The certificate should be a map".to_string()),
+    };
+
+    let field = |name: &str| map.get(&Value::Text(name.to_string())).cloned();
+
+    let tree = match field("tree") {
+        Some(_tree) => HashTree::from_value(strip_self_describe_tag(_tree))?,
+        None => return Err("The certificate has no tree".to_string()),
+    };
+
+    let signature = match field("signature") {
+        Some(Value::Bytes(_signature)) => _signature,
+        _ => return Err("The certificate has no signature".to_string()),
+    };
+
+    let delegation = match field("delegation") {
+        None => None,
+        Some(Value::Map(_delegation)) => {
+            let subnet_id = _delegation.get(&Value::Text("subnet_id".to_string()));
+            let certificate = _delegation.get(&Value::Text("certificate".to_string()));
+            match (subnet_id, certificate) {
+                (Some(Value::Bytes(_subnet_id)), Some(Value::Bytes(_certificate))) => {
+                    Some((_subnet_id.clone(), _certificate.clone()))
+                }
+                _ => return Err("Invalid delegation".to_string()),
+            }
+        }
+        _ => return Err("Invalid delegation".to_string()),
+    };
+
+    Ok((tree, signature, delegation))
+}
+
+fn verify_signature(
+    tree: &HashTree,
+    signature: &[u8],
+    der_public_key: &[u8],
+) -> Result<(), String> {
+    if der_public_key.len() <= BLS_DER_PREFIX_LENGTH {
+        return Err("Invalid public key".to_string());
+    }
+
+    let mut message = domain_separator(b"ic-state-root");
+    message.extend_from_slice(&tree.reconstruct());
+
+    ic_verify_bls_signature::verify_bls_signature(
+        signature,
+        &message,
+        &der_public_key[BLS_DER_PREFIX_LENGTH..],
+    )
+    .map_err(|_| "Invalid certificate signature".to_string())
+}
+
+// Method to check if the canister is part of the subnet the delegation is for
+fn check_canister_ranges(
+    delegation_tree: &HashTree,
+    subnet_id: &[u8],
+    canister_id: &Principal,
+) -> Result<(), String> {
+    let ranges = match delegation_tree.lookup(&[&b"subnet"[..], subnet_id, &b"canister_ranges"[..]])
+    {
+        LookupResult::Found(_ranges) => _ranges,
+        _ => return Err("The delegation has no canister ranges".to_string()),
+    };
+
+    let ranges: Vec<(Vec<u8>, Vec<u8>)> = match serde_cbor::from_slice(ranges) {
+        Ok(Value::Array(_ranges)) => _ranges
+            .into_iter()
+            .filter_map(|range| match range {
+                Value::Array(_range) => match _range.as_slice() {
+                    [Value::Bytes(_start), Value::Bytes(_end)] => {
+                        Some((_start.clone(), _end.clone()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect(),
+        _ => return Err("Invalid canister ranges".to_string()),
+    };
+
+    let canister_id = canister_id.as_slice();
+    match ranges
+        .iter()
+        .any(|(start, end)| start.as_slice() <= canister_id && canister_id <= end.as_slice())
+    {
+        true => Ok(()),
+        false => Err("The canister is not part of the delegated subnet".to_string()),
+    }
+}
+
+fn strip_self_describe_tag(value: Value) -> Value {
+    match value {
+        Value::Tag(55799, _value) => *_value,
+        _value => _value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificate_time() {
+        let now: u64 = 1_700_000_000_000_000_000;
+        let tree = |time: u64| {
+            let mut bytes = vec![];
+            let mut value = time;
+            loop {
+                let byte = (value & 0x7f) as u8;
+                value >>= 7;
+                if value == 0 {
+                    bytes.push(byte);
+                    break;
+                }
+                bytes.push(byte | 0x80);
+            }
+            HashTree::Labeled(b"time".to_vec(), Box::new(HashTree::Leaf(bytes)))
+        };
+
+        assert_eq!(
+            check_certificate_time(&tree(now - 1_000_000_000), now),
+            Ok(())
+        );
+        assert_eq!(
+            check_certificate_time(&tree(now - MAX_CERTIFICATE_AGE_NANOS - 1), now),
+            Err("The certificate is too old".to_string())
+        );
+        assert_eq!(
+            check_certificate_time(&HashTree::Empty, now),
+            Err("The certificate has no time".to_string())
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::io::{Read, Write};
+
+use candid::{CandidType, Deserialize, Principal};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// Version of the chunk protocol between the parent and child canisters, bumped on breaking changes
+pub static CHUNK_PROTOCOL_VERSION: u16 = 1;
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ChunkRequest {
+    // The chunk protocol version of the caller
+    pub version: u16,
+    pub group_identifier: Principal,
+    // Zero-based index of the requested chunk
+    pub chunk: u64,
+    pub max_bytes_per_chunk: u64,
+    // The codecs the caller can decode, in order of preference (`None` for callers that do not support compression)
+    pub accepted_codecs: Option<Vec<ChunkCodec>>,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ChunkCodec {
+    Identity,
+    Gzip,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DataChunk {
+    pub version: u16,
+    // SHA-256 hash of the complete serialized data, changes when the data changes between chunk calls
+    pub snapshot_id: Vec<u8>,
+    // The byte length of the complete serialized data
+    pub total_bytes: u64,
+    // The amount of chunks of the complete serialized data, always at least 1
+    pub chunk_count: u64,
+    pub chunk: u64,
+    pub bytes: Vec<u8>,
+    // SHA-256 hash of the bytes of this chunk
+    pub checksum: Vec<u8>,
+    // The codec the complete data is encoded with, the snapshot id, total bytes and checksums apply to the encoded data
+    pub codec: Option<ChunkCodec>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ChunkError {
+    UnsupportedVersion { requested: u16, supported: u16 },
+    InvalidChunkSize,
+    ChunkOutOfRange { chunk: u64, chunk_count: u64 },
+    SerializationFailed(String),
+    CompressionFailed(String),
+    // The caller is not the parent canister
+    Unauthorized,
+    // The checksum of a received chunk does not match its bytes
+    ChecksumMismatch { chunk: u64 },
+    // The data kept changing between the chunk calls
+    SnapshotChanged,
+}
+
+impl ChunkCodec {
+    // Method to pick the codec for a request, the first accepted codec that is supported is used
+    pub fn negotiate(accepted_codecs: &Option<Vec<ChunkCodec>>) -> ChunkCodec {
+        accepted_codecs
+            .as_ref()
+            .and_then(|codecs| codecs.first().copied())
+            .unwrap_or(ChunkCodec::Identity)
+    }
+
+    pub fn encode(&self, bytes: Vec<u8>) -> Result<Vec<u8>, ChunkError> {
+        match self {
+            ChunkCodec::Identity => Ok(bytes),
+            ChunkCodec::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder
+                    .write_all(&bytes)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|err| ChunkError::CompressionFailed(err.to_string()))
+            }
+        }
+    }
+
+    pub fn decode(&self, bytes: Vec<u8>) -> Result<Vec<u8>, ChunkError> {
+        match self {
+            ChunkCodec::Identity => Ok(bytes),
+            ChunkCodec::Gzip => {
+                let mut decoded = vec![];
+                GzDecoder::new(bytes.as_slice())
+                    .read_to_end(&mut decoded)
+                    .map(|_| decoded)
+                    .map_err(|err| ChunkError::CompressionFailed(err.to_string()))
+            }
+        }
+    }
+}
+
+impl DataChunk {
+    // Method to cut a chunk from the complete data, encoded with the given codec
+    // `snapshot_id` is the `checksum` of the complete data, passed in so it is not computed again for every chunk
+    pub fn from_bytes(
+        bytes: &[u8],
+        snapshot_id: &[u8],
+        request: &ChunkRequest,
+        codec: ChunkCodec,
+    ) -> Result<DataChunk, ChunkError> {
+        if request.version != CHUNK_PROTOCOL_VERSION {
+            return Err(ChunkError::UnsupportedVersion {
+                requested: request.version,
+                supported: CHUNK_PROTOCOL_VERSION,
+            });
+        }
+
+        if request.max_bytes_per_chunk == 0 {
+            return Err(ChunkError::InvalidChunkSize);
+        }
+
+        let total_bytes = bytes.len() as u64;
+        let chunk_count = std::cmp::max(1, total_bytes.div_ceil(request.max_bytes_per_chunk));
+
+        if request.chunk >= chunk_count {
+            return Err(ChunkError::ChunkOutOfRange {
+                chunk: request.chunk,
+                chunk_count,
+            });
+        }
+
+        let start = request.chunk * request.max_bytes_per_chunk;
+        let end = std::cmp::min(start + request.max_bytes_per_chunk, total_bytes);
+        let chunk_bytes = bytes[start as usize..end as usize].to_vec();
+
+        Ok(DataChunk {
+            version: CHUNK_PROTOCOL_VERSION,
+            snapshot_id: snapshot_id.to_vec(),
+            total_bytes,
+            chunk_count,
+            chunk: request.chunk,
+            checksum: checksum(&chunk_bytes),
+            bytes: chunk_bytes,
+            codec: Some(codec),
+        })
+    }
+
+    // Method to check the chunk against its checksum and the snapshot of the first chunk
+    pub fn verify(&self, first: &DataChunk) -> Result<(), ChunkError> {
+        if checksum(&self.bytes) != self.checksum {
+            return Err(ChunkError::ChecksumMismatch { chunk: self.chunk });
+        }
+
+        match self.snapshot_id == first.snapshot_id
+            && self.total_bytes == first.total_bytes
+            && self.chunk_count == first.chunk_count
+            && self.codec == first.codec
+        {
+            true => Ok(()),
+            false => Err(ChunkError::SnapshotChanged),
+        }
+    }
+}
+
+// Method to assemble and decode the complete serialized data from the verified chunks, ordered by chunk index
+pub fn assemble_chunks(chunks: Vec<DataChunk>) -> Result<Vec<u8>, ChunkError> {
+    let first = match chunks.first() {
+        Some(_first) => _first.clone(),
+        None => {
+            return Err(ChunkError::ChunkOutOfRange {
+                chunk: 0,
+                chunk_count: 0,
+            })
+        }
+    };
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(first.total_bytes as usize);
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        chunk.verify(&first)?;
+        if chunk.chunk != index as u64 {
+            return Err(ChunkError::ChunkOutOfRange {
+                chunk: chunk.chunk,
+                chunk_count: first.chunk_count,
+            });
+        }
+        bytes.extend(chunk.bytes);
+    }
+
+    if bytes.len() as u64 != first.total_bytes || checksum(&bytes) != first.snapshot_id {
+        return Err(ChunkError::SnapshotChanged);
+    }
+
+    first.codec.unwrap_or(ChunkCodec::Identity).decode(bytes)
+}
+
+pub fn checksum(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::Serialize;
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct InviteCodeRequest {
+    pub group_identifier: Principal,
+    // The amount of times the code can be redeemed, `None` for unlimited
+    pub max_uses: Option<u64>,
+    // Timestamp in nanoseconds after which the code can not be redeemed anymore, `None` for no expiry
+    pub expires_at: Option<u64>,
+    // The role that is given to the members that redeem the code, `None` for the `member` role
+    pub default_role: Option<String>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct InviteCode {
+    pub code: String,
+    pub group_identifier: Principal,
+    // The principal of the admin that created the code
+    pub created_by: Principal,
+    pub max_uses: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub default_role: Option<String>,
+    // The amount of times the code is redeemed
+    pub uses: u64,
+    pub revoked: bool,
+    pub created_at: u64,
+}
+
+impl Storable for InviteCode {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct InviteCodeRedemption {
+    pub principal: Principal,
+    pub redeemed_at: u64,
+}
+
+impl Storable for InviteCodeRedemption {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -1,2 +1,6 @@
+pub mod capacity_model;
+pub mod certification;
+pub mod chunk_model;
+pub mod invite_code_model;
 pub mod invite_model;
 pub mod member_model;
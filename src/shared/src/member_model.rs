@@ -44,6 +44,61 @@ pub struct Join {
     pub roles: Vec<String>,
     pub updated_at: u64,
     pub created_at: u64,
+    // Moderation status within the group, `None` for active members (and joins stored before the status existed)
+    pub status: Option<MemberStatus>,
+    // Per-group profile fields (for example a nickname or a short bio), keyed by the field keys of the group schema
+    pub profile: Option<HashMap<String, String>>,
+}
+
+// Definition of a per-group profile field, the schema of the fields is defined by the group canister
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfileFieldDefinition {
+    pub key: String,
+    // The maximum length of the value in characters
+    pub max_length: u64,
+    // Admin-only fields can only be set by members with the edit member permission
+    pub admin_only: bool,
+}
+
+// Name of the permission in the group roles that allows to mute and suspend members (`edit` action)
+// the protected `owner`, `admin` and `moderator` roles have this permission by default
+pub static MODERATION_PERMISSION: &str = "member_moderation";
+
+// Moderation status of a member within a group, `until` is a timestamp in nanoseconds
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MemberStatus {
+    #[default]
+    Active,
+    // The member keeps read access but loses all other permissions
+    Muted {
+        until: u64,
+    },
+    // The member loses all permissions
+    Suspended {
+        until: u64,
+        reason: String,
+    },
+}
+
+impl MemberStatus {
+    pub fn get_until(&self) -> Option<u64> {
+        match self {
+            MemberStatus::Active => None,
+            MemberStatus::Muted { until } | MemberStatus::Suspended { until, .. } => Some(*until),
+        }
+    }
+}
+
+impl Join {
+    // Method to get the status at a point in time, an expired status is `Active` even if it is not reinstated yet
+    pub fn get_status(&self, now: u64) -> MemberStatus {
+        match &self.status {
+            Some(_status) if _status.get_until().is_some_and(|until| until > now) => {
+                _status.clone()
+            }
+            _ => MemberStatus::Active,
+        }
+    }
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
@@ -51,6 +106,24 @@ pub struct Invite {
     pub invite_type: InviteType,
     pub updated_at: u64,
     pub created_at: u64,
+    // Answers to the join questionnaire of the group, only set on user requests for private groups
+    pub answers: Option<Vec<JoinAnswer>>,
+}
+
+// Question of the join questionnaire of a private group, the questionnaire is defined by the group canister
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JoinQuestion {
+    pub id: String,
+    pub question: String,
+    pub required: bool,
+    // The maximum length of the answer in characters
+    pub max_length: u64,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JoinAnswer {
+    pub question_id: String,
+    pub answer: String,
 }
 
 #[derive(CandidType, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -71,6 +144,19 @@ pub struct JoinedMemberResponse {
     pub member_identifier: Principal,
     pub principal: Principal,
     pub roles: Vec<String>,
+    // Optional on the wire so responses of child canisters that are not upgraded yet can still be decoded
+    pub status: Option<MemberStatus>,
+    // Optional on the wire for the same reason as `status`
+    pub profile: Option<HashMap<String, String>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct MemberEntryResponse {
+    // The canister that stores the member entry
+    pub canister: Principal,
+    // The identifier of the member entry, `None` when the entry was handed over to a new sibling canister
+    pub identifier: Option<Principal>,
+    pub member: Member,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
@@ -80,3 +166,62 @@ pub struct InviteMemberResponse {
     pub principal: Principal,
     pub invite: Invite,
 }
+
+// A group member that matched a search query, a higher score is a better match
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct MemberSearchResult {
+    pub member: JoinedMemberResponse,
+    pub score: u32,
+}
+
+// Counts of the data stored on a child canister, gathered by the parent canister for the fleet metrics
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct MemberStats {
+    pub entries: u64,
+    pub joins: u64,
+    pub invites: u64,
+}
+
+// Token bucket rate limit, every call takes a token from a bucket that holds up to `capacity` tokens
+// the bucket is refilled with `refill_per_minute` tokens per minute
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct RateLimit {
+    pub capacity: u64,
+    pub refill_per_minute: u64,
+}
+
+// Configuration that is pushed by the parent canister to the child canisters
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ChildConfig {
+    // Maximum amount of member entries a child stores before a sibling is spawned, `None` leaves it up to the canister capacity
+    pub max_entries: Option<u64>,
+    // Upper bound of the bytes per chunk a child returns for the chunked data calls
+    pub max_bytes_per_chunk: u64,
+    // Rate limit of the membership mutations per calling principal, `None` for no limit
+    pub principal_rate_limit: Option<RateLimit>,
+    // Rate limit of the membership mutations per group, `None` for no limit
+    pub group_rate_limit: Option<RateLimit>,
+}
+
+impl Default for ChildConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: None,
+            max_bytes_per_chunk: 2_000_000,
+            principal_rate_limit: None,
+            group_rate_limit: None,
+        }
+    }
+}
+
+impl Storable for ChildConfig {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}